@@ -4,8 +4,21 @@ use crate::{
         Offset,
         Version,
     },
+    errors::{
+        FileErrors,
+        ParseErrors,
+    },
     graph::Graph,
+    manifest::{
+        self,
+        Manifest,
+    },
     offsets::OffsetLists,
+    profile::{
+        Layout,
+        Profile,
+    },
+    versionlib,
 };
 use anyhow::Context as _;
 use byteorder::{
@@ -13,44 +26,383 @@ use byteorder::{
     ReadBytesExt as _,
     WriteBytesExt as _,
 };
+use clap::ValueEnum;
 use regex_lite::Regex;
+use serde::Deserialize;
 use std::{
-    collections::BTreeMap,
-    fs::File,
-    io::Read,
-    path::Path,
+    collections::{
+        BTreeMap,
+        HashMap,
+    },
+    fmt::{
+        self,
+        Display,
+        Formatter,
+        Write as _,
+    },
+    fs::{
+        self,
+        File,
+    },
+    io::{
+        BufRead,
+        BufReader,
+        Read,
+        Write,
+    },
+    path::{
+        Path,
+        PathBuf,
+    },
 };
-use walkdir::WalkDir;
+
+type Mappings = Vec<(Id, Offset)>;
+
+/// How address bins are stored.
+#[derive(Clone, Copy, Default, Deserialize, PartialEq, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum BinFormat {
+    /// The binary form loaded at runtime
+    #[default]
+    Binary,
+
+    /// The text form written by `bin2txt`, which can be reviewed as a diff
+    Text,
+}
+
+/// A structural problem found within an address bin, referring to entries by their index.
+enum Problem {
+    /// The bin claims to hold more entries than fit within the file.
+    Truncated { len: u64, available: usize },
+    /// There are bytes left over after the last entry.
+    TrailingBytes(usize),
+    /// An entry has a smaller id than the entry before it.
+    Unsorted { index: usize, id: Id, previous: Id },
+    /// An entry has the same id as an earlier entry.
+    DuplicateId { index: usize, first: usize, id: Id },
+    /// An entry has the same offset as an earlier entry.
+    DuplicateOffset {
+        index: usize,
+        first: usize,
+        offset: Offset,
+    },
+}
+
+impl Problem {
+    fn find_all(mappings: &[(Id, Offset)]) -> Vec<Self> {
+        let mut result = Vec::new();
+        let mut ids = HashMap::new();
+        let mut offsets = HashMap::new();
+        for (index, (id, offset)) in mappings.iter().enumerate() {
+            if let Some(previous) = index.checked_sub(1).map(|x| mappings[x].0) {
+                if *id < previous {
+                    result.push(Self::Unsorted {
+                        index,
+                        id: *id,
+                        previous,
+                    });
+                }
+            }
+            if let Some(first) = ids.insert(*id, index) {
+                ids.insert(*id, first);
+                result.push(Self::DuplicateId {
+                    index,
+                    first,
+                    id: *id,
+                });
+            }
+            if let Some(first) = offsets.insert(*offset, index) {
+                offsets.insert(*offset, first);
+                result.push(Self::DuplicateOffset {
+                    index,
+                    first,
+                    offset: *offset,
+                });
+            }
+        }
+        result
+    }
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Truncated { len, available } => write!(
+                f,
+                "len is {len}, but the file only holds {available} entries"
+            ),
+            Self::TrailingBytes(count) => {
+                write!(f, "found {count} trailing bytes after the last entry")
+            }
+            Self::Unsorted {
+                index,
+                id,
+                previous,
+            } => write!(
+                f,
+                "entry {index}: id '{id}' is smaller than the id of the previous entry ({previous})"
+            ),
+            Self::DuplicateId { index, first, id } => {
+                write!(
+                    f,
+                    "entry {index}: id '{id}' is a duplicate of entry {first}"
+                )
+            }
+            Self::DuplicateOffset {
+                index,
+                first,
+                offset,
+            } => write!(
+                f,
+                "entry {index}: offset '{offset}' is a duplicate of entry {first}"
+            ),
+        }
+    }
+}
 
 pub struct AddressBin {
     mappings: Vec<(Id, Offset)>,
+    path: PathBuf,
+    problems: Vec<Problem>,
 }
 
 impl AddressBin {
-    fn parse<R: Read>(src: &mut R) -> anyhow::Result<Self> {
+    /// The version of the text form written by [`AddressBin::to_text`].
+    const TEXT_FORMAT: &'static str = "1";
+
+    /// Parses an address bin, along with any structural problems found within it. Entries past
+    /// the end of the file are dropped, and entries which fail to decode are reported by index.
+    fn parse<R: Read>(
+        src: &mut R,
+        errors: &mut FileErrors,
+    ) -> anyhow::Result<(Mappings, Vec<Problem>)> {
+        let mut bytes = Vec::new();
+        src.read_to_end(&mut bytes)
+            .context("error while reading address bin")?;
+        let mut src = &bytes[..];
         let mut read_u64 = || {
             src.read_u64::<LittleEndian>()
                 .context("error while reading address bin")
         };
         let len = read_u64().context("failed to read len")?;
+
+        let mut problems = Vec::new();
+        let available = (bytes.len() - 8) / 16;
+        let len = match usize::try_from(len) {
+            Ok(len) if len <= available => len,
+            _ => {
+                problems.push(Problem::Truncated { len, available });
+                available
+            }
+        };
+
         let mut mappings = Vec::new();
-        for _ in 0..len {
-            let id = read_u64()
-                .context("failed to read id")?
-                .try_into()
-                .context("read an id with an invalid representation")?;
-            let offset = read_u64()
-                .context("failed to read offset")?
-                .try_into()
-                .context("read an offset too large to fit into a u32")?;
-            mappings.push((id, Offset(offset)));
+        for index in 0..len {
+            let id = read_u64().context("failed to read id")?;
+            let offset = read_u64().context("failed to read offset")?;
+            let mapping = Id::try_from(id)
+                .context("read an id with an invalid representation")
+                .and_then(|id| {
+                    let offset = u32::try_from(offset)
+                        .context("read an offset too large to fit into a u32")?;
+                    Ok((id, Offset(offset)))
+                });
+            mappings.extend(errors.check(index, mapping)?);
         }
-        Ok(Self { mappings })
+
+        let trailing = bytes.len() - 8 - len * 16;
+        if trailing != 0 && problems.is_empty() {
+            problems.push(Problem::TrailingBytes(trailing));
+        }
+        problems.extend(Problem::find_all(&mappings));
+        Ok((mappings, problems))
+    }
+
+    /// Loads an address bin laid out as the given profile expects. Bins which carry their version
+    /// must carry the given version, if one is given. Structural problems do not fail the load,
+    /// see [`AddressBin::report_problems`].
+    pub fn load(
+        path: &Path,
+        profile: Profile,
+        version: Option<Version>,
+        errors: &mut ParseErrors,
+    ) -> anyhow::Result<Self> {
+        let (found, mappings, problems) = Self::read(path, profile, errors)?;
+        if let (Some(version), Some(found)) = (version, found) {
+            if found != version {
+                anyhow::bail!(
+                    "expected address bin for version '{version}', but found '{found}': {path:?}"
+                );
+            }
+        }
+        Ok(Self {
+            mappings,
+            path: path.to_owned(),
+            problems,
+        })
+    }
+
+    /// Reads the mappings of an address bin, along with its version if the layout carries one,
+    /// and any structural problems found within it.
+    fn read(
+        path: &Path,
+        profile: Profile,
+        errors: &mut ParseErrors,
+    ) -> anyhow::Result<(Option<Version>, Mappings, Vec<Problem>)> {
+        let mut file =
+            File::open(path).with_context(|| format!("failed to open file: {}", path.display()))?;
+        let (version, mappings, problems) = match profile.layout() {
+            Layout::Flat => {
+                let (mappings, problems) = Self::parse(&mut file, &mut errors.file(path))
+                    .with_context(|| format!("failed to parse address bin: {}", path.display()))?;
+                (None, mappings, problems)
+            }
+            Layout::Compressed { format, .. } => {
+                let (version, mappings) = versionlib::read_compressed(
+                    &mut BufReader::new(file),
+                    format,
+                    profile.pointer_size(),
+                )
+                .with_context(|| format!("failed to parse address bin: {}", path.display()))?;
+                let problems = Problem::find_all(&mappings);
+                (Some(version), mappings, problems)
+            }
+            Layout::Csv => {
+                let (version, mappings) =
+                    versionlib::read_csv(&mut BufReader::new(file), &mut errors.file(path))
+                        .with_context(|| {
+                            format!("failed to parse address bin: {}", path.display())
+                        })?;
+                let problems = Problem::find_all(&mappings);
+                (Some(version), mappings, problems)
+            }
+        };
+        Ok((version, mappings, problems))
+    }
+
+    /// Loads an address bin from its text form, which must hold the given version.
+    fn load_text(path: &Path, version: Version) -> anyhow::Result<Self> {
+        let file =
+            File::open(path).with_context(|| format!("failed to open file: {}", path.display()))?;
+        let (found, mappings) = Self::parse_text(&mut BufReader::new(file))
+            .with_context(|| format!("failed to parse address bin text: {}", path.display()))?;
+        if found != version {
+            anyhow::bail!(
+                "expected address bin text for version '{version}', but found '{found}': {path:?}"
+            );
+        }
+        let problems = Problem::find_all(&mappings);
+        Ok(Self {
+            mappings,
+            path: path.to_owned(),
+            problems,
+        })
+    }
+
+    /// Writes the given mappings as text, one `id<TAB>offset` per line sorted by id, after a
+    /// header carrying the format of the text and the version of the bin.
+    fn to_text(version: Version, mappings: &[(Id, Offset)]) -> String {
+        let mut sorted = mappings.to_vec();
+        sorted.sort_by_key(|x| x.0);
+        let mut result = format!("format\t{}\nversion\t{version}\n", Self::TEXT_FORMAT);
+        for (id, offset) in sorted {
+            writeln!(result, "{id}\t{offset}").expect("writing to a string can not fail");
+        }
+        result
+    }
+
+    fn parse_text<R: BufRead>(src: &mut R) -> anyhow::Result<(Version, Mappings)> {
+        let mut buffer = String::new();
+        macro_rules! read_line {
+            () => {{
+                buffer.clear();
+                src.read_line(&mut buffer)
+            }};
+        }
+        let format_pattern =
+            Regex::new(r"format\t(\d+)").context("failed to build format pattern")?;
+        let version_pattern =
+            Regex::new(r"version\t(\S+)").context("failed to build version pattern")?;
+        let mapping_pattern = Regex::new(r"^(\d+)\t0x([\dA-Fa-f]+)\s*$")
+            .context("failed to build mapping pattern")?;
+
+        read_line!().context("failed to read format")?;
+        let captures = format_pattern
+            .captures(&buffer)
+            .context("failed to match format pattern")?;
+        if &captures[1] != Self::TEXT_FORMAT {
+            anyhow::bail!("unsupported format: {}", &captures[1]);
+        }
+
+        read_line!().context("failed to read version")?;
+        let captures = version_pattern
+            .captures(&buffer)
+            .context("failed to match version pattern")?;
+        let version = captures[1].parse()?;
+
+        let mut mappings = Vec::new();
+        for line in 3.. {
+            match read_line!() {
+                Ok(0) => break,
+                Ok(_) if buffer.trim().is_empty() => (),
+                Ok(_) => {
+                    let captures = mapping_pattern.captures(&buffer).with_context(|| {
+                        format!("failed to match mapping pattern on line {line}")
+                    })?;
+                    let id = captures[1]
+                        .parse::<u64>()
+                        .ok()
+                        .and_then(|x| Id::try_from(x).ok())
+                        .with_context(|| format!("failed to parse id on line {line}"))?;
+                    let offset = u32::from_str_radix(&captures[2], 16)
+                        .with_context(|| format!("failed to parse offset on line {line}"))?;
+                    mappings.push((id, Offset(offset)));
+                }
+                Err(err) => {
+                    return Err(err).with_context(|| format!("failed to read line {line}"));
+                }
+            }
+        }
+        Ok((version, mappings))
+    }
+
+    /// Merges the given mappings which are missing from this bin into it, keeping every existing
+    /// mapping as it is and all of them ordered by id. Fails if any of the mappings would assign a
+    /// new offset to an existing id, or a new id to an existing offset.
+    fn extend(&self, mappings: &[(Id, Offset)]) -> anyhow::Result<Vec<(Id, Offset)>> {
+        let ids = self.mappings.iter().copied().collect::<BTreeMap<_, _>>();
+        let offsets = self
+            .mappings
+            .iter()
+            .map(|(id, offset)| (*offset, *id))
+            .collect::<BTreeMap<_, _>>();
+        let mut added = Vec::new();
+        for (id, offset) in mappings {
+            match (ids.get(id), offsets.get(offset)) {
+                (Some(existing), _) if existing != offset => anyhow::bail!(
+                    "id '{id}' would move from offset '{existing}' to offset '{offset}'"
+                ),
+                (Some(_), _) => (),
+                (None, Some(existing)) => anyhow::bail!(
+                    "offset '{offset}' would change from id '{existing}' to id '{id}'"
+                ),
+                (None, None) => added.push((*id, *offset)),
+            }
+        }
+        let mut result = self.mappings.clone();
+        result.extend(added);
+        result.sort_by_key(|x| x.0);
+        Ok(result)
     }
 
     pub fn iter(&self) -> impl Iterator<Item = &(Id, Offset)> {
         self.mappings.iter()
     }
+
+    /// Prints the structural problems found within the bin as warnings.
+    pub fn report_problems(&self) {
+        report_problems(&self.path, &self.problems);
+    }
 }
 
 pub struct AddressBins {
@@ -58,46 +410,29 @@ pub struct AddressBins {
 }
 
 impl AddressBins {
-    pub fn parse_all(root_dir: &Path) -> anyhow::Result<Self> {
+    pub fn parse_all(manifest: &Manifest, errors: &mut ParseErrors) -> anyhow::Result<Self> {
         println!("parsing address bins...");
 
-        let pattern = Regex::new(r"version-(\d+)-(\d+)-(\d+)-(\d+)\.bin")
-            .context("failed to build file name pattern")?;
         let mut bins = BTreeMap::new();
-
-        for dir_entry in WalkDir::new(root_dir) {
-            let dir_entry = dir_entry.with_context(|| {
-                format!("error while locating address bins in directory: {root_dir:?}")
-            })?;
-            let path = dir_entry.path();
-            let metadata = dir_entry
-                .metadata()
-                .with_context(|| format!("failed to get metadata for file: {path:?}"))?;
-            if metadata.is_file() {
-                if let Some(file_name) = path.file_name().and_then(|x| x.to_str()) {
-                    if let Some(captures) = pattern.captures(file_name) {
-                        let version: Version =
-                            (&captures[1], &captures[2], &captures[3], &captures[4])
-                                .try_into()
-                                .with_context(|| {
-                                    format!("failed to parse version from file name: {path:?}")
-                                })?;
-                        let bin = {
-                            let mut file = File::open(path)
-                                .with_context(|| format!("failed to open file: {path:?}"))?;
-                            AddressBin::parse(&mut file)
-                                .with_context(|| format!("failed to parse address bin: {path:?}"))
-                        }?;
-                        bins.insert(version, bin);
-                    }
+        for (version, path) in manifest.existing_bins() {
+            let bin = match manifest.format() {
+                BinFormat::Binary => {
+                    AddressBin::load(path, manifest.profile(), Some(version), errors)?
                 }
-            }
+                BinFormat::Text => AddressBin::load_text(path, version)?,
+            };
+            check_problems(path, &bin.problems)?;
+            bins.insert(version, bin);
         }
 
         Ok(Self { bins })
     }
 
-    pub fn iter(&self) -> impl Iterator<Item = (&Version, &AddressBin)> {
+    pub fn get(&self, key: Version) -> Option<&AddressBin> {
+        self.bins.get(&key)
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (&Version, &AddressBin)> {
         self.bins.iter()
     }
 
@@ -112,50 +447,412 @@ impl AddressBins {
     }
 }
 
+/// Fails if any structural problems were found, since seeding ids from such a bin could assign
+/// the same id to several offsets.
+fn check_problems(path: &Path, problems: &[Problem]) -> anyhow::Result<()> {
+    if !problems.is_empty() {
+        let problems = problems.iter().map(ToString::to_string).collect::<Vec<_>>();
+        anyhow::bail!(
+            "address bin is malformed: {path:?}\n\t{}",
+            problems.join("\n\t")
+        );
+    }
+    Ok(())
+}
+
+/// Prints the given problems as warnings. These go to stderr, since `diff-bins` writes its result
+/// to stdout.
+fn report_problems(path: &Path, problems: &[Problem]) {
+    if !problems.is_empty() {
+        eprintln!(
+            "warning: found {} problems in address bin: {}",
+            problems.len(),
+            path.display()
+        );
+        for problem in problems {
+            eprintln!("\t{problem}");
+        }
+    }
+}
+
+/// Finds the module and version of an address bin from its file name, e.g.
+/// `CreationKit-version-1-10-163-0.bin`. The module of bins without one is empty.
+pub fn parse_file_name(pattern: &Regex, path: &Path) -> anyhow::Result<Option<(String, Version)>> {
+    let Some(captures) = path
+        .file_name()
+        .and_then(|x| x.to_str())
+        .and_then(|x| pattern.captures(x))
+    else {
+        return Ok(None);
+    };
+    let module = captures.get(1).map_or("", |x| x.as_str());
+    let version = (&captures[2], &captures[3], &captures[4], &captures[5])
+        .try_into()
+        .with_context(|| format!("failed to parse version from file name: {}", path.display()))?;
+    Ok(Some((module.to_owned(), version)))
+}
+
+/// The name of the address bin of the given version, e.g. `version-1-10-163-0.bin`.
+pub fn file_name(version: Version, profile: Profile, format: BinFormat) -> String {
+    let extension = match format {
+        BinFormat::Binary => profile.extension(),
+        BinFormat::Text => "txt",
+    };
+    format!(
+        "{}-{}-{}-{}-{}.{extension}",
+        profile.file_prefix(),
+        version[0],
+        version[1],
+        version[2],
+        version[3]
+    )
+}
+
+pub fn file_name_pattern(profile: Profile, format: BinFormat) -> anyhow::Result<Regex> {
+    let extension = match format {
+        BinFormat::Binary => profile.extension(),
+        BinFormat::Text => "txt",
+    };
+    Regex::new(&format!(
+        r"^{}{}-(\d+)-(\d+)-(\d+)-(\d+)\.{extension}$",
+        manifest::MODULE_PATTERN,
+        profile.file_prefix()
+    ))
+    .context("failed to build file name pattern")
+}
+
+fn write_bin(path: &Path, mappings: &[(Id, Offset)]) -> anyhow::Result<()> {
+    let mut file =
+        File::create(path).with_context(|| format!("failed to create file: {}", path.display()))?;
+    write_mappings(&mut file, mappings)
+        .with_context(|| format!("failed write for address bin: {}", path.display()))
+}
+
+/// Writes the given mappings of a version to an address bin in the given format.
+fn write_bin_as(
+    path: &Path,
+    version: Version,
+    mappings: &[(Id, Offset)],
+    profile: Profile,
+    format: BinFormat,
+) -> anyhow::Result<()> {
+    let result = match (format, profile.layout()) {
+        (BinFormat::Text, _) => fs::write(path, AddressBin::to_text(version, mappings))
+            .with_context(|| format!("failed to write file: {}", path.display())),
+        (BinFormat::Binary, Layout::Flat) => return write_bin(path, mappings),
+        (BinFormat::Binary, Layout::Compressed { format, executable }) => {
+            let mut file = File::create(path)
+                .with_context(|| format!("failed to create file: {}", path.display()))?;
+            versionlib::write_compressed(
+                &mut file,
+                version,
+                mappings,
+                format,
+                executable,
+                profile.pointer_size(),
+            )
+        }
+        (BinFormat::Binary, Layout::Csv) => {
+            fs::write(path, versionlib::write_csv(version, mappings))
+                .with_context(|| format!("failed to write file: {}", path.display()))
+        }
+    };
+    result.with_context(|| format!("failed write for address bin: {}", path.display()))
+}
+
+fn write_mappings<W: Write>(dst: &mut W, mappings: &[(Id, Offset)]) -> anyhow::Result<()> {
+    let mut write_u64 = |x| {
+        dst.write_u64::<LittleEndian>(x)
+            .context("error while writing address bin")
+    };
+
+    write_u64(mappings.len() as u64)?;
+    for (id, offset) in mappings {
+        write_u64(id.get())?;
+        write_u64(u64::from(offset.0))?;
+    }
+
+    Ok(())
+}
+
+/// Writes a bin for every version which is missing one. When extending, the bins which already
+/// exist are rewritten to also include any offsets they are missing, keeping all of their existing
+/// mappings intact.
 pub fn write_bins(
-    root_dir: &Path,
+    manifest: &Manifest,
     graph: &Graph,
     offset_lists: &OffsetLists,
     address_bins: &AddressBins,
+    extend: bool,
 ) -> anyhow::Result<()> {
     println!("writing bins...");
 
     for (version, offset_list) in offset_lists.iter() {
-        if !address_bins.bins.contains_key(version) {
-            let mut file = {
-                let path = root_dir.join(format!(
-                    "version-{}-{}-{}-{}.bin",
-                    version[0], version[1], version[2], version[3]
-                ));
-                if path.exists() {
-                    anyhow::bail!("can not write to file because it already exists: {path:?}");
+        let mappings = {
+            let mut v = offset_list
+                .iter()
+                .map(|(offset, mapping)| (graph.get(mapping.ix), *offset))
+                .collect::<Vec<_>>();
+            v.sort_by_key(|x| x.0);
+            v
+        };
+
+        if let Some(address_bin) = address_bins.bins.get(version) {
+            if extend {
+                let extended = address_bin
+                    .extend(&mappings)
+                    .with_context(|| format!("failed to extend address bin: {version}"))?;
+                let added = extended.len() - address_bin.mappings.len();
+                if added != 0 {
+                    println!("\textending {version} with {added} mappings");
+                    let path = &address_bin.path;
+                    let temp = {
+                        let mut x = path.clone().into_os_string();
+                        x.push(".tmp");
+                        PathBuf::from(x)
+                    };
+                    write_bin_as(
+                        &temp,
+                        *version,
+                        &extended,
+                        manifest.profile(),
+                        manifest.format(),
+                    )?;
+                    fs::rename(&temp, path).with_context(|| {
+                        format!(
+                            "failed to replace file: {} with file: {}",
+                            path.display(),
+                            temp.display()
+                        )
+                    })?;
                 }
-                File::create(&path).with_context(|| format!("failed to create file: {path:?}"))
-            }?;
-            let mut write_u64 = |x| {
-                file.write_u64::<LittleEndian>(x)
-                    .with_context(|| format!("failed write for address bin: {version}"))
-            };
+            }
+        } else {
+            let path = manifest.bin_path(*version);
+            if path.exists() {
+                anyhow::bail!("can not write to file because it already exists: {path:?}");
+            }
+            write_bin_as(
+                &path,
+                *version,
+                &mappings,
+                manifest.profile(),
+                manifest.format(),
+            )?;
+        }
+    }
 
-            let mappings = {
-                let mut v = offset_list
-                    .iter()
-                    .map(|(offset, mapping)| {
-                        let id = graph.get(mapping.ix);
-                        (id.get(), u64::from(offset.0))
-                    })
-                    .collect::<Vec<_>>();
-                v.sort_by_key(|x| x.0);
-                v
-            };
+    Ok(())
+}
 
-            write_u64(mappings.len() as u64)?;
-            for (id, offset) in mappings {
-                write_u64(id)?;
-                write_u64(offset)?;
+/// Dumps an address bin as text. The version is taken from the bin itself if it carries one, or
+/// otherwise from its file name, unless one is given.
+pub fn bin_to_text(
+    input: &Path,
+    version: Option<Version>,
+    output: Option<&Path>,
+    profile: Profile,
+) -> anyhow::Result<()> {
+    let (found, mappings, problems) =
+        AddressBin::read(input, profile, &mut ParseErrors::default())?;
+    report_problems(input, &problems);
+    let version = match version.or(found) {
+        Some(x) => x,
+        None => parse_file_name(&file_name_pattern(profile, BinFormat::Binary)?, input)?
+            .map(|(_, x)| x)
+            .with_context(|| {
+                format!(
+                    "failed to find a version in the file name, please specify one: {}",
+                    input.display()
+                )
+            })?,
+    };
+    let path = output.map_or_else(|| input.with_extension("txt"), Path::to_path_buf);
+    fs::write(&path, AddressBin::to_text(version, &mappings))
+        .with_context(|| format!("failed to write file: {}", path.display()))
+}
+
+/// Rebuilds an address bin from its text form. The bin is written next to the text, named after
+/// the version in its header, unless an output is given.
+pub fn text_to_bin(input: &Path, output: Option<&Path>, profile: Profile) -> anyhow::Result<()> {
+    let (version, mappings) = {
+        let file = File::open(input)
+            .with_context(|| format!("failed to open file: {}", input.display()))?;
+        AddressBin::parse_text(&mut BufReader::new(file))
+            .with_context(|| format!("failed to parse address bin text: {}", input.display()))
+    }?;
+    let path = output.map_or_else(
+        || input.with_file_name(file_name(version, profile, BinFormat::Binary)),
+        Path::to_path_buf,
+    );
+    write_bin_as(&path, version, &mappings, profile, BinFormat::Binary)
+}
+
+/// Checks each of the given address bins, laid out as the given profile expects, for structural
+/// problems, and reports them.
+pub fn validate_bins(paths: &[PathBuf], profile: Profile) -> anyhow::Result<()> {
+    let mut malformed = 0usize;
+    for path in paths {
+        let (_, _, problems) = AddressBin::read(path, profile, &mut ParseErrors::default())?;
+        if problems.is_empty() {
+            println!("{}: ok", path.display());
+        } else {
+            malformed += 1;
+            println!("{}: found {} problems", path.display(), problems.len());
+            for problem in problems {
+                println!("\t{problem}");
             }
         }
     }
 
+    if malformed != 0 {
+        anyhow::bail!("found {malformed} malformed address bins");
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        AddressBin,
+        BinFormat,
+    };
+    use crate::{
+        common::{
+            Id,
+            Offset,
+        },
+        errors::ParseErrors,
+        profile::Profile,
+    };
+    use std::{
+        io::BufReader,
+        path::{
+            Path,
+            PathBuf,
+        },
+    };
+
+    #[test]
+    fn test_problems() -> anyhow::Result<()> {
+        let id = |x: u64| Id::try_from(x);
+        let mut bytes = Vec::new();
+        super::write_mappings(
+            &mut bytes,
+            &[
+                (id(1)?, Offset(0x1000)),
+                (id(3)?, Offset(0x3000)),
+                (id(2)?, Offset(0x2000)),
+                (id(2)?, Offset(0x1000)),
+            ],
+        )?;
+
+        let mut trailing = bytes.clone();
+        trailing.extend([0xCC; 3]);
+        let problems = AddressBin::parse(
+            &mut &trailing[..],
+            &mut ParseErrors::default().file(Path::new("test")),
+        )?
+        .1
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+        assert_eq!(
+            problems,
+            [
+                "found 3 trailing bytes after the last entry",
+                "entry 2: id '2' is smaller than the id of the previous entry (3)",
+                "entry 3: id '2' is a duplicate of entry 2",
+                "entry 3: offset '0x1000' is a duplicate of entry 0",
+            ]
+        );
+
+        let truncated = &bytes[..bytes.len() - 24];
+        let (mappings, problems) = AddressBin::parse(
+            &mut &truncated[..],
+            &mut ParseErrors::default().file(Path::new("test")),
+        )?;
+        assert_eq!(mappings.len(), 2);
+        assert_eq!(
+            problems[0].to_string(),
+            "len is 4, but the file only holds 2 entries"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_text_round_trip() -> anyhow::Result<()> {
+        let mut bytes = Vec::new();
+        let id = |x: u64| Id::try_from(x);
+        super::write_mappings(
+            &mut bytes,
+            &[(id(1)?, Offset(0x1000)), (id(20)?, Offset(0x1A_2B3C))],
+        )?;
+
+        let bin = AddressBin {
+            mappings: AddressBin::parse(
+                &mut &bytes[..],
+                &mut ParseErrors::default().file(Path::new("test")),
+            )?
+            .0,
+            path: PathBuf::new(),
+            problems: Vec::new(),
+        };
+        let text = AddressBin::to_text("1.10.163".parse()?, &bin.mappings);
+        assert_eq!(
+            text,
+            "format\t1\nversion\tv1.10.163.0\n1\t0x1000\n20\t0x1A2B3C\n"
+        );
+
+        let (version, mappings) = AddressBin::parse_text(&mut BufReader::new(text.as_bytes()))?;
+        assert!(version == "1.10.163.0".parse()?);
+        let mut round_trip = Vec::new();
+        super::write_mappings(&mut round_trip, &mappings)?;
+        assert_eq!(bytes, round_trip);
+
+        let unsorted = [(id(20)?, Offset(0x1A_2B3C)), (id(1)?, Offset(0x1000))];
+        let text = AddressBin::to_text(version, &unsorted);
+        let sorted = [unsorted[1], unsorted[0]];
+        assert!(AddressBin::parse_text(&mut BufReader::new(text.as_bytes()))?.1 == sorted);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_file_name() -> anyhow::Result<()> {
+        let pattern = super::file_name_pattern(Profile::Fallout4, BinFormat::Binary)?;
+        let parse = |x: &str| super::parse_file_name(&pattern, Path::new(x));
+        let version = "1.10.130".parse()?;
+        assert!(parse("version-1-10-130-0.bin")? == Some((String::new(), version)));
+        assert!(
+            parse("CreationKit-version-1-10-130-0.bin")?
+                == Some(("CreationKit".to_owned(), version))
+        );
+        assert!(parse("versionlib-1-10-130-0.bin")?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_extend() -> anyhow::Result<()> {
+        let id = |x: u64| Id::try_from(x);
+        let bin = AddressBin {
+            mappings: vec![(id(1)?, Offset(0x1000)), (id(3)?, Offset(0x3000))],
+            path: PathBuf::new(),
+            problems: Vec::new(),
+        };
+
+        let extended = bin
+            .extend(&[
+                (id(1)?, Offset(0x1000)),
+                (id(2)?, Offset(0x2000)),
+                (id(3)?, Offset(0x3000)),
+            ])?
+            .into_iter()
+            .map(|(id, offset)| (id.get(), offset.0))
+            .collect::<Vec<_>>();
+        assert_eq!(extended, [(1, 0x1000), (2, 0x2000), (3, 0x3000)]);
+
+        assert!(bin.extend(&[(id(1)?, Offset(0x1010))]).is_err());
+        assert!(bin.extend(&[(id(4)?, Offset(0x3000))]).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,431 @@
+use crate::{
+    common::Offset,
+    errors::{
+        FileErrors,
+        ParseErrors,
+    },
+    graph::{
+        Graph,
+        Source,
+    },
+    offsets::{
+        OffsetList,
+        OffsetLists,
+    },
+    xrefs::XrefList,
+};
+use anyhow::Context as _;
+use regex_lite::{
+    Captures,
+    Regex,
+};
+use std::{
+    collections::{
+        hash_map::DefaultHasher,
+        BTreeMap,
+        HashMap,
+    },
+    hash::{
+        Hash as _,
+        Hasher as _,
+    },
+    io::BufRead,
+};
+
+pub struct Instruction {
+    pub offset: Offset,
+    pub bytes: Vec<u8>,
+    /// A bit is set for every byte which encodes a reference to another address, and so will
+    /// change whenever code or data is relocated.
+    pub wildcards: u16,
+    /// A hash of the instruction text, with all operands that refer to addresses masked out, so
+    /// that it stays stable when code or data moves between versions.
+    pub hash: u64,
+    padding: bool,
+}
+
+impl Instruction {
+    /// Marks the bytes which encode the given target as wildcards, whether it is encoded relative
+    /// to the next instruction or as an absolute address.
+    fn mask_target(&mut self, base_address: u64, target: u64) {
+        let next = base_address + u64::from(self.offset.0) + self.bytes.len() as u64;
+        #[allow(clippy::cast_possible_truncation)]
+        let relative = (target.wrapping_sub(next).cast_signed() as i32).to_le_bytes();
+        let absolute = target.to_le_bytes();
+        #[allow(clippy::cast_possible_truncation)]
+        let truncated = (target as u32).to_le_bytes();
+        for needle in [&relative[..], &absolute[..], &truncated[..]] {
+            let found = (1..self.bytes.len())
+                .rev()
+                .find(|i| self.bytes[*i..].starts_with(needle));
+            if let Some(i) = found {
+                for j in i..(i + needle.len()).min(16) {
+                    self.wildcards |= 1 << j;
+                }
+                break;
+            }
+        }
+    }
+
+    pub fn is_wildcard(&self, index: usize) -> bool {
+        index < 16 && self.wildcards & (1 << index) != 0
+    }
+}
+
+/// Rewrites the text of an instruction into a form that does not depend on where code or data is
+/// located within the image.
+struct Normalizer {
+    base_address: u64,
+    name_pattern: Regex,
+    target_pattern: Regex,
+    immediate_pattern: Regex,
+    displacement_pattern: Regex,
+}
+
+impl Normalizer {
+    fn new(base_address: u64) -> anyhow::Result<Self> {
+        Ok(Self {
+            base_address,
+            name_pattern: Regex::new(
+                r"\b(?:sub|loc|locret|off|unk|byte|word|dword|qword|xmmword|ymmword|stru|asc|flt|dbl|nullsub|j_sub|def|jpt)_[\dA-Fa-f]+\b",
+            )
+            .context("failed to build name pattern")?,
+            target_pattern: Regex::new(r"\b(?:[A-Za-z]+_)?([\dA-Fa-f]{9,16})h?\b")
+                .context("failed to build target pattern")?,
+            immediate_pattern: Regex::new(r"\b([\dA-Fa-f]+)h\b")
+                .context("failed to build immediate pattern")?,
+            displacement_pattern: Regex::new(r"rip([+-])[\dA-Fa-f]+h?")
+                .context("failed to build displacement pattern")?,
+        })
+    }
+
+    fn normalize(&self, text: &str) -> String {
+        let text = text.split(';').next().unwrap_or_default();
+        let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        let text = self.name_pattern.replace_all(&text, "<addr>");
+        let text = self
+            .displacement_pattern
+            .replace_all(&text, "rip${1}<disp>");
+        self.immediate_pattern
+            .replace_all(&text, |captures: &Captures| {
+                match u64::from_str_radix(&captures[1], 16) {
+                    Ok(x) if x >= self.base_address => "<addr>".to_owned(),
+                    _ => captures[0].to_owned(),
+                }
+            })
+            .into_owned()
+    }
+
+    /// Collects every address mentioned within the text of an instruction, either as a literal or
+    /// as part of an auto-generated name.
+    fn targets(&self, text: &str) -> Vec<u64> {
+        let text = text.split(';').next().unwrap_or_default();
+        self.target_pattern
+            .captures_iter(text)
+            .filter_map(|x| u64::from_str_radix(&x[1], 16).ok())
+            .filter(|x| *x >= self.base_address)
+            .collect()
+    }
+
+    fn is_padding(text: &str) -> bool {
+        matches!(
+            text.split(' ').next(),
+            Some("int3" | "nop" | "align" | "db")
+        ) || text == "int 3"
+    }
+}
+
+pub struct AsmList {
+    instructions: Vec<Instruction>,
+}
+
+impl AsmList {
+    const ASM_PATTERN: &'static str = r"asm\t([\dA-Fa-f]+)\t([\dA-Fa-f]*)\t([^\r\n]*)";
+
+    pub fn load(offset_list: &OffsetList, errors: &mut ParseErrors) -> anyhow::Result<Self> {
+        let mut file = offset_list.open("idaexport_asm.txt")?;
+        let path = offset_list.directory().join("idaexport_asm.txt");
+        Self::parse(
+            &mut file,
+            offset_list.base_address(),
+            &mut errors.file(&path),
+        )
+        .context("failed to parse idaexport_asm.txt")
+    }
+
+    fn parse<R: BufRead>(
+        idaexport: &mut R,
+        base_address: u64,
+        errors: &mut FileErrors,
+    ) -> anyhow::Result<Self> {
+        let normalizer = Normalizer::new(base_address)?;
+        let mut instructions =
+            OffsetList::parse_generic(idaexport, Self::ASM_PATTERN, errors, |captures| {
+                let offset = OffsetList::parse_offset(base_address, &captures[1])?;
+                let bytes = Self::parse_bytes(&captures[2])
+                    .with_context(|| format!("failed to parse bytes: {}", &captures[2]))?;
+                let text = normalizer.normalize(&captures[3]);
+                let mut hasher = DefaultHasher::new();
+                text.hash(&mut hasher);
+                let mut instruction = Instruction {
+                    offset: Offset(offset),
+                    bytes,
+                    wildcards: 0,
+                    hash: hasher.finish(),
+                    padding: Normalizer::is_padding(&text),
+                };
+                for target in normalizer.targets(&captures[3]) {
+                    instruction.mask_target(base_address, target);
+                }
+                Ok(instruction)
+            })?;
+        instructions.sort_by_key(|x| x.offset);
+        Ok(Self { instructions })
+    }
+
+    fn parse_bytes(string: &str) -> anyhow::Result<Vec<u8>> {
+        if !string.len().is_multiple_of(2) {
+            anyhow::bail!("odd number of hex digits");
+        }
+        (0..string.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&string[i..i + 2], 16).map_err(Into::into))
+            .collect()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Instruction> {
+        self.instructions.iter()
+    }
+
+    /// Masks the operands of every instruction that is the source of an xref, for references which
+    /// could not be recovered from the instruction text alone.
+    pub fn mask_xrefs(&mut self, xrefs: &XrefList, base_address: u64) {
+        let mut xrefs = xrefs.iter().peekable();
+        for instruction in &mut self.instructions {
+            while xrefs.next_if(|x| x.from < instruction.offset).is_some() {}
+            while let Some(xref) = xrefs.next_if(|x| x.from == instruction.offset) {
+                instruction.mask_target(base_address, base_address + u64::from(xref.to.0));
+            }
+        }
+    }
+
+    /// Hashes the instructions of every function, ignoring any padding which trails the body.
+    pub fn function_hashes(&self, offset_list: &OffsetList) -> BTreeMap<Offset, u64> {
+        let mut functions: BTreeMap<Offset, Vec<&Instruction>> = BTreeMap::new();
+        for instruction in self.iter() {
+            if let Some(function) = offset_list.function_containing(instruction.offset) {
+                functions.entry(function).or_default().push(instruction);
+            }
+        }
+
+        functions
+            .into_iter()
+            .map(|(function, mut instructions)| {
+                while instructions.last().is_some_and(|x| x.padding) {
+                    instructions.pop();
+                }
+                let mut hasher = DefaultHasher::new();
+                for instruction in instructions {
+                    instruction.hash.hash(&mut hasher);
+                }
+                (function, hasher.finish())
+            })
+            .collect()
+    }
+}
+
+/// Maps each hash that occurs exactly once to the function that produced it.
+fn unique_hashes(hashes: &BTreeMap<Offset, u64>) -> HashMap<u64, Offset> {
+    let mut unique: HashMap<u64, Option<Offset>> = HashMap::new();
+    for (function, hash) in hashes {
+        unique
+            .entry(*hash)
+            .and_modify(|x| *x = None)
+            .or_insert(Some(*function));
+    }
+    unique
+        .into_iter()
+        .filter_map(|(hash, function)| function.map(|x| (hash, x)))
+        .collect()
+}
+
+/// Matches functions whose normalized instructions hash to a value that is unique within both
+/// versions.
+pub fn match_functions(
+    graph: &mut Graph,
+    offset_lists: &OffsetLists,
+    errors: &mut ParseErrors,
+) -> anyhow::Result<()> {
+    println!("matching functions by instruction hashes...");
+
+    let mut hashes = BTreeMap::new();
+    for (version, offset_list) in offset_lists.iter() {
+        if !offset_list.has_idaexport() {
+            println!("\tskipping '{version}', which has no idaexport files");
+            continue;
+        }
+        let functions = AsmList::load(offset_list, errors)
+            .with_context(|| format!("failed to load instructions for version '{version}'"))?
+            .function_hashes(offset_list);
+        hashes.insert(*version, unique_hashes(&functions));
+    }
+
+    for ((left_version, left_offsets), (right_version, right_offsets)) in
+        offset_lists.adjacent_pairs()
+    {
+        let (Some(left_hashes), Some(right_hashes)) =
+            (hashes.get(left_version), hashes.get(right_version))
+        else {
+            continue;
+        };
+        let correspondence = graph.correspondence(left_offsets, right_offsets);
+        let mut matches = 0usize;
+        for (hash, left) in left_hashes {
+            let Some(right) = right_hashes.get(hash) else {
+                continue;
+            };
+            if correspondence.is_left_matched(*left) || correspondence.is_right_matched(*right) {
+                continue;
+            }
+            let left_node = left_offsets.get(*left).map(|x| x.ix);
+            let right_node = right_offsets.get(*right).map(|x| x.ix);
+            if let (Some(left_node), Some(right_node)) = (left_node, right_node) {
+                graph.add_edge(left_node, right_node, Source::Asm);
+                matches += 1;
+            }
+        }
+        println!("\t'{left_version}' -> '{right_version}': {matches} functions");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        AsmList,
+        Normalizer,
+    };
+    use crate::{
+        common,
+        errors::ParseErrors,
+        manifest::Manifest,
+        offsets::OffsetLists,
+    };
+    use std::{
+        fmt::Write as _,
+        fs,
+        io::BufReader,
+        path::Path,
+    };
+
+    #[test]
+    fn test_normalize() -> anyhow::Result<()> {
+        let normalizer = Normalizer::new(0x1_4000_0000)?;
+        assert_eq!(normalizer.normalize("call    sub_1401234A0"), "call <addr>");
+        assert_eq!(
+            normalizer.normalize("mov     rax, cs:qword_143A2B4C8"),
+            "mov rax, cs:<addr>"
+        );
+        assert_eq!(
+            normalizer.normalize("lea     rcx, aHelloWorld ; \"Hello world\""),
+            "lea rcx, aHelloWorld"
+        );
+        assert_eq!(
+            normalizer.normalize("mov     rax, 143A2B4C8h"),
+            "mov rax, <addr>"
+        );
+        assert_eq!(normalizer.normalize("mov     ecx, 140h"), "mov ecx, 140h");
+        assert_eq!(
+            normalizer.normalize("lea     rdx, [rip+1A2Bh]"),
+            "lea rdx, [rip+<disp>]"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_instructions() -> anyhow::Result<()> {
+        let mut buffer = BufReader::new(
+            &br"version	1
+asm	140001004	E897120000	call    sub_1400022A0
+asm	140001000	4883EC28	sub     rsp, 28h
+asm	140001009	CC	int     3
+"[..],
+        );
+        let result = AsmList::parse(
+            &mut buffer,
+            0x1_4000_0000,
+            &mut ParseErrors::default().file(Path::new("test")),
+        )?;
+        assert_eq!(
+            result
+                .iter()
+                .map(|x| (x.offset.0, x.bytes.clone(), x.wildcards, x.padding))
+                .collect::<Vec<_>>(),
+            [
+                (0x1000, vec![0x48, 0x83, 0xEC, 0x28], 0b0, false),
+                (0x1004, vec![0xE8, 0x97, 0x12, 0x00, 0x00], 0b11110, false),
+                (0x1009, vec![0xCC], 0b0, true),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_match_functions() -> anyhow::Result<()> {
+        let root = common::test_dir("match-asm");
+        // the first function calls a moved function, and the bodies of the last two only collide
+        // on the left
+        for (version, text, last) in [
+            ("1.10.130", 0x1000, "xor     eax, eax"),
+            ("1.10.138", 0x1100, "mov     eax, 1"),
+        ] {
+            let directory = root.join(version);
+            common::write_idaexport(
+                &directory,
+                &[
+                    (text, text + 0x10),
+                    (text + 0x10, text + 0x20),
+                    (text + 0x20, text + 0x30),
+                ],
+                &[],
+                &[],
+            )?;
+            let mut asm = "version\t1\n".to_owned();
+            for (offset, bytes, instruction) in [
+                (
+                    text,
+                    "E800000000",
+                    format!("call    sub_14000{:04X}", text + 0x20),
+                ),
+                (text + 5, "C3", "retn".to_owned()),
+                (text + 6, "CC", "int     3".to_owned()),
+                (text + 0x10, "31C0", "xor     eax, eax".to_owned()),
+                (text + 0x12, "C3", "retn".to_owned()),
+                (text + 0x20, "31C0", last.to_owned()),
+                (text + 0x22, "C3", "retn".to_owned()),
+            ] {
+                writeln!(asm, "asm\t14000{offset:04X}\t{bytes}\t{instruction}")?;
+            }
+            fs::write(directory.join("idaexport_asm.txt"), asm)?;
+        }
+
+        let manifest = Manifest::load(&root, None)?;
+        let (offset_lists, mut graph) =
+            OffsetLists::parse_all(&manifest, &[], &mut ParseErrors::default())?;
+        super::match_functions(&mut graph, &offset_lists, &mut ParseErrors::default())?;
+
+        let ((_, left), (_, right)) = offset_lists
+            .adjacent_pairs()
+            .next()
+            .expect("expected two versions");
+        let result = graph
+            .correspondence(left, right)
+            .iter()
+            .map(|(x, y)| (x.0, y.0))
+            .collect::<Vec<_>>();
+        assert_eq!(result, [(0x1000, 0x1100)]);
+        fs::remove_dir_all(root)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,154 @@
+use crate::{
+    addrlib::AddressBin,
+    common::{
+        Id,
+        Offset,
+    },
+    errors::ParseErrors,
+    profile::Profile,
+};
+use anyhow::Context as _;
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    fs,
+    path::Path,
+};
+
+#[derive(Default)]
+struct BinDiff {
+    added: Vec<(Id, Offset)>,
+    removed: Vec<(Id, Offset)>,
+    /// The id, along with its old and new offset.
+    changed: Vec<(Id, Offset, Offset)>,
+}
+
+impl BinDiff {
+    fn new<L, R>(old: L, new: R) -> Self
+    where
+        L: IntoIterator<Item = (Id, Offset)>,
+        R: IntoIterator<Item = (Id, Offset)>,
+    {
+        let old = old.into_iter().collect::<BTreeMap<_, _>>();
+        let new = new.into_iter().collect::<BTreeMap<_, _>>();
+        let mut result = Self::default();
+        for (id, old_offset) in &old {
+            match new.get(id) {
+                Some(new_offset) if new_offset != old_offset => {
+                    result.changed.push((*id, *old_offset, *new_offset));
+                }
+                Some(_) => (),
+                None => result.removed.push((*id, *old_offset)),
+            }
+        }
+        result.added = new
+            .into_iter()
+            .filter(|(id, _)| !old.contains_key(id))
+            .collect();
+        result
+    }
+
+    fn to_text(&self) -> String {
+        let mut result = String::new();
+        macro_rules! line {
+            ($($arg:tt)*) => {
+                writeln!(result, $($arg)*).expect("writing to a string can not fail")
+            };
+        }
+
+        line!("{} added:", self.added.len());
+        for (id, offset) in &self.added {
+            line!("\t{id}\t{offset}");
+        }
+        line!("{} removed:", self.removed.len());
+        for (id, offset) in &self.removed {
+            line!("\t{id}\t{offset}");
+        }
+        line!("{} changed:", self.changed.len());
+        for (id, old, new) in &self.changed {
+            line!("\t{id}\t{old} -> {new}");
+        }
+        result
+    }
+
+    fn to_json(&self) -> String {
+        let mappings = |mappings: &[(Id, Offset)]| {
+            mappings
+                .iter()
+                .map(|(id, offset)| format!(r#"{{"id":{id},"offset":{}}}"#, offset.0))
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        let changed = self
+            .changed
+            .iter()
+            .map(|(id, old, new)| format!(r#"{{"id":{id},"old":{},"new":{}}}"#, old.0, new.0))
+            .collect::<Vec<_>>()
+            .join(",");
+        let mut result = format!(
+            r#"{{"added":[{}],"removed":[{}],"changed":[{changed}]}}"#,
+            mappings(&self.added),
+            mappings(&self.removed),
+        );
+        result.push('\n');
+        result
+    }
+}
+
+/// Compares two address bins, and writes the ids which were added, removed, or whose offset
+/// changed going from the old bin to the new one.
+pub fn diff_bins(
+    old: &Path,
+    new: &Path,
+    profile: Profile,
+    json: bool,
+    output: Option<&Path>,
+) -> anyhow::Result<()> {
+    let old = AddressBin::load(old, profile, None, &mut ParseErrors::default())?;
+    let new = AddressBin::load(new, profile, None, &mut ParseErrors::default())?;
+    old.report_problems();
+    new.report_problems();
+    let diff = BinDiff::new(old.iter().copied(), new.iter().copied());
+    let result = if json { diff.to_json() } else { diff.to_text() };
+
+    if let Some(path) = output {
+        fs::write(path, result).with_context(|| format!("failed to write file: {}", path.display()))
+    } else {
+        print!("{result}");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BinDiff;
+    use crate::common::{
+        Id,
+        Offset,
+    };
+
+    #[test]
+    fn test_bin_diff() -> anyhow::Result<()> {
+        let old = [
+            (Id::try_from(1)?, Offset(0x1000)),
+            (Id::try_from(2)?, Offset(0x2000)),
+            (Id::try_from(3)?, Offset(0x3000)),
+        ];
+        let new = [
+            (Id::try_from(1)?, Offset(0x1000)),
+            (Id::try_from(3)?, Offset(0x3010)),
+            (Id::try_from(4)?, Offset(0x4000)),
+        ];
+        let diff = BinDiff::new(old, new);
+        assert_eq!(
+            diff.to_json(),
+            r#"{"added":[{"id":4,"offset":16384}],"removed":[{"id":2,"offset":8192}],"changed":[{"id":3,"old":12288,"new":12304}]}
+"#
+        );
+        assert_eq!(
+            diff.to_text(),
+            "1 added:\n\t4\t0x4000\n1 removed:\n\t2\t0x2000\n1 changed:\n\t3\t0x3000 -> 0x3010\n"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,152 @@
+use crate::{
+    graph::{
+        Graph,
+        Source,
+    },
+    offsets::OffsetLists,
+};
+
+/// Matches functions positionally between two matched functions, whenever the functions in
+/// between have the same sequence of sizes in both versions.
+pub fn match_function_sizes(graph: &mut Graph, offset_lists: &OffsetLists) {
+    println!("matching functions by size sequences...");
+
+    for ((left_version, left_offsets), (right_version, right_offsets)) in
+        offset_lists.adjacent_pairs()
+    {
+        let left_functions = left_offsets.functions();
+        let right_functions = right_offsets.functions();
+        let correspondence = graph.correspondence(left_offsets, right_offsets);
+
+        let mut anchors: Vec<(usize, usize)> = Vec::new();
+        for (left, function) in left_functions.iter().enumerate() {
+            let right = correspondence
+                .left(function.start)
+                .and_then(|x| right_functions.binary_search_by_key(&x, |x| x.start).ok());
+            if let Some(right) = right {
+                if anchors.last().is_none_or(|x| x.1 < right) {
+                    anchors.push((left, right));
+                }
+            }
+        }
+
+        let mut edges = Vec::new();
+        for window in anchors.windows(2) {
+            let [(left_begin, right_begin), (left_end, right_end)] = *window else {
+                continue;
+            };
+            let left_between = &left_functions[left_begin + 1..left_end];
+            let right_between = &right_functions[right_begin + 1..right_end];
+            if left_between.is_empty()
+                || left_between.len() != right_between.len()
+                || left_between
+                    .iter()
+                    .zip(right_between)
+                    .any(|(left, right)| left.size() != right.size())
+            {
+                continue;
+            }
+
+            for (left, right) in left_between.iter().zip(right_between) {
+                if correspondence.is_left_matched(left.start)
+                    || correspondence.is_right_matched(right.start)
+                {
+                    continue;
+                }
+                let left = left_offsets.get(left.start).map(|x| x.ix);
+                let right = right_offsets.get(right.start).map(|x| x.ix);
+                if let (Some(left), Some(right)) = (left, right) {
+                    edges.push((left, right));
+                }
+            }
+        }
+
+        for (left, right) in &edges {
+            graph.add_edge(*left, *right, Source::FunctionSize);
+        }
+        println!(
+            "\t'{left_version}' -> '{right_version}': {} functions",
+            edges.len()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        common::{
+            self,
+            Offset,
+        },
+        errors::ParseErrors,
+        graph::Source,
+        manifest::Manifest,
+        offsets::OffsetLists,
+    };
+    use std::fs;
+
+    #[test]
+    fn test_match_function_sizes() -> anyhow::Result<()> {
+        let root = common::test_dir("function-sizes");
+        // the two functions between the first anchors keep their sizes, while the one between the
+        // last anchors grows
+        common::write_idaexport(
+            &root.join("1.10.130"),
+            &[
+                (0x1000, 0x1010),
+                (0x1010, 0x1030),
+                (0x1030, 0x1038),
+                (0x1040, 0x1050),
+                (0x1050, 0x1060),
+                (0x1060, 0x1070),
+            ],
+            &[],
+            &[],
+        )?;
+        common::write_idaexport(
+            &root.join("1.10.138"),
+            &[
+                (0x1100, 0x1110),
+                (0x1110, 0x1130),
+                (0x1130, 0x1138),
+                (0x1140, 0x1150),
+                (0x1150, 0x1168),
+                (0x1170, 0x1180),
+            ],
+            &[],
+            &[],
+        )?;
+
+        let manifest = Manifest::load(&root, None)?;
+        let (offset_lists, mut graph) =
+            OffsetLists::parse_all(&manifest, &[], &mut ParseErrors::default())?;
+        let ((_, left), (_, right)) = offset_lists
+            .adjacent_pairs()
+            .next()
+            .expect("expected two versions");
+        for (x, y) in [(0x1000, 0x1100), (0x1040, 0x1140), (0x1060, 0x1170)] {
+            let x = left.get(Offset(x)).expect("expected left function").ix;
+            let y = right.get(Offset(y)).expect("expected right function").ix;
+            graph.add_edge(x, y, Source::Diff);
+        }
+        super::match_function_sizes(&mut graph, &offset_lists);
+
+        let result = graph
+            .correspondence(left, right)
+            .iter()
+            .map(|(x, y)| (x.0, y.0))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            result,
+            [
+                (0x1000, 0x1100),
+                (0x1010, 0x1110),
+                (0x1030, 0x1130),
+                (0x1040, 0x1140),
+                (0x1060, 0x1170),
+            ]
+        );
+        fs::remove_dir_all(root)?;
+        Ok(())
+    }
+}
@@ -2,6 +2,11 @@ use nonmax::{
     NonMaxU64,
     TryFromIntError,
 };
+use serde::{
+    de,
+    Deserialize,
+    Deserializer,
+};
 use std::{
     fmt::{
         self,
@@ -9,9 +14,10 @@ use std::{
         Formatter,
     },
     ops::Index,
+    str::FromStr,
 };
 
-#[derive(Clone, Copy, Default, Eq, Ord, PartialEq, PartialOrd)]
+#[derive(Clone, Copy, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct Id(NonMaxU64);
 
 impl Id {
@@ -38,7 +44,7 @@ impl TryFrom<u64> for Id {
     }
 }
 
-#[derive(Clone, Copy, Eq, Ord, PartialEq, PartialOrd)]
+#[derive(Clone, Copy, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct Offset(pub u32);
 
 impl Display for Offset {
@@ -47,7 +53,7 @@ impl Display for Offset {
     }
 }
 
-#[derive(Clone, Copy, Eq, Ord, PartialEq, PartialOrd)]
+#[derive(Clone, Copy, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct Version([u16; 4]);
 
 impl Display for Version {
@@ -64,6 +70,12 @@ impl Index<usize> for Version {
     }
 }
 
+impl From<[u16; 4]> for Version {
+    fn from(value: [u16; 4]) -> Self {
+        Self(value)
+    }
+}
+
 impl TryFrom<(&str, &str, &str)> for Version {
     type Error = anyhow::Error;
 
@@ -89,3 +101,88 @@ impl TryFrom<(&str, &str, &str, &str)> for Version {
         ]))
     }
 }
+
+impl FromStr for Version {
+    type Err = anyhow::Error;
+
+    fn from_str(string: &str) -> anyhow::Result<Self> {
+        let parts = string
+            .strip_prefix('v')
+            .unwrap_or(string)
+            .split(['.', '-'])
+            .collect::<Vec<_>>();
+        match parts[..] {
+            [major, minor, revision] => (major, minor, revision).try_into(),
+            [major, minor, revision, build] => (major, minor, revision, build).try_into(),
+            _ => {
+                anyhow::bail!("expected a version of the form `1.10.163` or `1.10.163.0`: {string}")
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Version {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+/// Creates an empty directory for a test to lay out its inputs in.
+#[cfg(test)]
+pub fn test_dir(name: &str) -> std::path::PathBuf {
+    let path = std::env::temp_dir().join(format!("libgen-{name}-{}", std::process::id()));
+    if path.exists() {
+        std::fs::remove_dir_all(&path).expect("failed to remove test directory");
+    }
+    std::fs::create_dir_all(&path).expect("failed to create test directory");
+    path
+}
+
+/// Writes a minimal idaexport directory for a test, with a `.text` segment at 0x1000 and a `.data`
+/// segment at 0x3000. Offsets are relative to the base address of 0x140000000, and the files which
+/// are not given any contents only hold their header.
+#[cfg(test)]
+pub fn write_idaexport(
+    directory: &std::path::Path,
+    functions: &[(u32, u32)],
+    globals: &[u32],
+    xrefs: &[(u32, u32)],
+) -> std::io::Result<()> {
+    use std::fs;
+
+    let lines = |lines: Vec<String>| format!("version\t1\n{}", lines.join(""));
+    fs::create_dir_all(directory)?;
+    fs::write(
+        directory.join("idaexport_base.txt"),
+        "version\t1\nbaseaddress\t140000000\n",
+    )?;
+    fs::write(
+        directory.join("idaexport_segment.txt"),
+        "version\t1\nsegment\t140001000\t140002000\t.text\nsegment\t140003000\t140004000\t.data\n",
+    )?;
+    for file_name in [
+        "idaexport_asm.txt",
+        "idaexport_name.txt",
+        "idaexport_string.txt",
+        "idaexport_vtable.txt",
+    ] {
+        fs::write(directory.join(file_name), "version\t1\n")?;
+    }
+    let functions = functions
+        .iter()
+        .map(|(start, end)| format!("func\t14000{start:04X}\t14000{end:04X}\n"))
+        .collect();
+    fs::write(directory.join("idaexport_func.txt"), lines(functions))?;
+    let globals = globals
+        .iter()
+        .map(|x| format!("global\t14000{x:04X}\tint\n"))
+        .collect();
+    fs::write(directory.join("idaexport_global.txt"), lines(globals))?;
+    let xrefs = xrefs
+        .iter()
+        .map(|(from, to)| format!("xref\t14000{from:04X}\t14000{to:04X}\n"))
+        .collect();
+    fs::write(directory.join("idaexport_xrefs.txt"), lines(xrefs))
+}
@@ -1,32 +1,45 @@
-use crate::common::{
-    Offset,
-    Version,
+use crate::{
+    common::{
+        Offset,
+        Version,
+    },
+    errors::{
+        FileErrors,
+        ParseErrors,
+    },
+    manifest::Manifest,
+    offsets::{
+        OffsetList,
+        OffsetLists,
+    },
+    segments::SegmentList,
 };
 use anyhow::Context as _;
 use regex_lite::Regex;
 use std::{
-    fs::File,
+    collections::BTreeMap,
+    fmt::Write as _,
+    fs::{
+        self,
+        File,
+    },
     io::{
         BufRead,
         BufReader,
     },
-    num::ParseIntError,
     path::Path,
 };
-use walkdir::WalkDir;
 
 pub struct Diff {
     pub left: Offset,
     pub right: Offset,
 }
 
-impl TryFrom<(&str, &str)> for Diff {
-    type Error = ParseIntError;
-
-    fn try_from(value: (&str, &str)) -> Result<Self, Self::Error> {
+impl Diff {
+    fn parse(base_address: u64, left: &str, right: &str) -> anyhow::Result<Self> {
         Ok(Self {
-            left: Offset(u32::from_str_radix(value.0, 16)?),
-            right: Offset(u32::from_str_radix(value.1, 16)?),
+            left: Offset(OffsetList::parse_offset(base_address, left)?),
+            right: Offset(OffsetList::parse_offset(base_address, right)?),
         })
     }
 }
@@ -38,11 +51,21 @@ pub struct DiffList {
 }
 
 impl DiffList {
-    fn parse_diffs<R: BufRead>(file: &mut R) -> anyhow::Result<Vec<Diff>> {
+    /// How far outside the range spanned by its ordered neighbours a diff may land before it is
+    /// reported, so that functions which merely swapped places with nearby ones are kept.
+    const MAX_LOCAL_DISTANCE: u32 = 0x1000;
+
+    fn parse_diffs<R: BufRead>(
+        file: &mut R,
+        base_address: u64,
+        errors: &mut FileErrors,
+    ) -> anyhow::Result<Vec<Diff>> {
         let mut buffer = String::new();
+        let mut line = 0;
         macro_rules! read_line {
             () => {{
                 buffer.clear();
+                line += 1;
                 file.read_line(&mut buffer)
                     .context("error while reading from diff file")
             }};
@@ -62,10 +85,10 @@ impl DiffList {
                     }
                 }
                 Err(err) => return Err(err),
-            };
+            }
         }
 
-        let diff_pattern = Regex::new(r"0x14([\dA-Fa-f]+)\t0x14([\dA-Fa-f]+)")
+        let diff_pattern = Regex::new(r"0x([\dA-Fa-f]+)\t0x([\dA-Fa-f]+)")
             .context("failed to build diff pattern")?;
         let mut diffs = Vec::new();
         loop {
@@ -73,13 +96,15 @@ impl DiffList {
                 Ok(0) => Ok(diffs),
                 Ok(_) if buffer.trim().is_empty() => Ok(diffs),
                 Ok(_) => {
-                    let captures = diff_pattern
+                    let diff = diff_pattern
                         .captures(&buffer)
-                        .context("failed to match diff pattern")?;
-                    let diff = (&captures[1], &captures[2])
-                        .try_into()
-                        .with_context(|| format!("failed to construct diff from line: {buffer}"))?;
-                    diffs.push(diff);
+                        .context("failed to match diff pattern")
+                        .and_then(|x| {
+                            Diff::parse(base_address, &x[1], &x[2]).with_context(|| {
+                                format!("failed to construct diff from line: {buffer}")
+                            })
+                        });
+                    diffs.extend(errors.check(line, diff)?);
                     continue;
                 }
                 Err(err) => Err(err),
@@ -90,6 +115,82 @@ impl DiffList {
     pub fn iter(&self) -> impl Iterator<Item = &Diff> {
         self.diffs.iter()
     }
+
+    /// Finds the indices of all diffs which do not belong to the longest chain of diffs that
+    /// preserves the relative order of offsets within each segment of the left version, and which
+    /// land further than [`Self::MAX_LOCAL_DISTANCE`] from their neighbours in that chain.
+    fn order_outliers(&self, left_segments: &SegmentList) -> Vec<usize> {
+        let mut groups: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+        for (i, diff) in self.diffs.iter().enumerate() {
+            let segment = left_segments
+                .find(diff.left)
+                .map_or("", |x| x.name.as_str());
+            groups.entry(segment).or_default().push(i);
+        }
+
+        let mut outliers = Vec::new();
+        for mut group in groups.into_values() {
+            group.sort_by_key(|x| self.diffs[*x].left);
+            let rights = group
+                .iter()
+                .map(|x| self.diffs[*x].right)
+                .collect::<Vec<_>>();
+            let chain = longest_increasing_subsequence(&rights);
+            let mut keep = vec![false; group.len()];
+            for i in &chain {
+                keep[*i] = true;
+            }
+            for (i, right) in rights.iter().enumerate() {
+                let pos = chain.partition_point(|x| *x < i);
+                let low = pos
+                    .checked_sub(1)
+                    .map_or(0, |x| rights[chain[x]].0)
+                    .saturating_sub(Self::MAX_LOCAL_DISTANCE);
+                let high = chain
+                    .get(pos)
+                    .map_or(u32::MAX, |x| rights[*x].0)
+                    .saturating_add(Self::MAX_LOCAL_DISTANCE);
+                if (low..=high).contains(&right.0) {
+                    keep[i] = true;
+                }
+            }
+            outliers.extend(
+                group
+                    .into_iter()
+                    .zip(keep)
+                    .filter_map(|(i, keep)| (!keep).then_some(i)),
+            );
+        }
+
+        outliers.sort_unstable();
+        outliers
+    }
+}
+
+/// Finds the indices of the longest strictly increasing subsequence.
+fn longest_increasing_subsequence<T: Ord>(values: &[T]) -> Vec<usize> {
+    // `tails[k]` is the index of the smallest value that ends an increasing subsequence of length
+    // `k + 1`, and `previous[i]` is the index preceding `i` in the subsequence ending at `i`.
+    let mut tails: Vec<usize> = Vec::new();
+    let mut previous = vec![None; values.len()];
+    for (i, value) in values.iter().enumerate() {
+        let pos = tails.partition_point(|x| values[*x] < *value);
+        previous[i] = pos.checked_sub(1).map(|x| tails[x]);
+        if pos == tails.len() {
+            tails.push(i);
+        } else {
+            tails[pos] = i;
+        }
+    }
+
+    let mut result = Vec::with_capacity(tails.len());
+    let mut next = tails.last().copied();
+    while let Some(i) = next {
+        result.push(i);
+        next = previous[i];
+    }
+    result.reverse();
+    result
 }
 
 pub struct DiffLists {
@@ -97,48 +198,28 @@ pub struct DiffLists {
 }
 
 impl DiffLists {
-    pub fn parse_all(root_dir: &Path) -> anyhow::Result<Self> {
+    pub fn parse_all(manifest: &Manifest, errors: &mut ParseErrors) -> anyhow::Result<Self> {
         println!("parsing diffs...");
 
-        let pattern = Regex::new(r"(\d+)\.(\d+)\.(\d+)_(\d+)\.(\d+)\.(\d+)\.txt")
-            .context("failed to build file name pattern")?;
         let mut lists = Vec::new();
-
-        for dir_entry in WalkDir::new(root_dir) {
-            let dir_entry = dir_entry.with_context(|| {
-                format!("error while locating diff files in directory: {root_dir:?}")
-            })?;
-            let path = dir_entry.path();
-            let metadata = dir_entry
-                .metadata()
-                .with_context(|| format!("failed to parse metadata from file: {path:?}"))?;
-            if metadata.is_file() {
-                if let Some(file_name) = path.file_name().and_then(|x| x.to_str()) {
-                    if let Some(captures) = pattern.captures(file_name) {
-                        let parse_version = |i1, i2, i3| {
-                            Version::try_from((&captures[i1], &captures[i2], &captures[i3]))
-                                .with_context(|| {
-                                    format!("failed to parse version from file name: {path:?}")
-                                })
-                        };
-                        let left = parse_version(1, 2, 3)?;
-                        let right = parse_version(4, 5, 6)?;
-                        if left == right {
-                            anyhow::bail!(
-                                "found a diff file that maps from one version to itself: {path:?}"
-                            );
-                        }
-                        let diffs = {
-                            let file = File::open(path)
-                                .with_context(|| format!("failed to open file: {path:?}"))?;
-                            let mut file = BufReader::new(file);
-                            DiffList::parse_diffs(&mut file)
-                                .with_context(|| format!("error while parsing file: {path:?}"))
-                        }?;
-                        lists.push(DiffList { diffs, left, right });
-                    }
-                }
-            }
+        for file in manifest.diffs() {
+            let path = &file.path;
+            let diffs = {
+                let file = File::open(path)
+                    .with_context(|| format!("failed to open file: {}", path.display()))?;
+                let mut file = BufReader::new(file);
+                DiffList::parse_diffs(
+                    &mut file,
+                    manifest.profile().base_address(),
+                    &mut errors.file(path),
+                )
+                .with_context(|| format!("error while parsing file: {}", path.display()))
+            }?;
+            lists.push(DiffList {
+                diffs,
+                left: file.left,
+                right: file.right,
+            });
         }
 
         Ok(Self { lists })
@@ -147,12 +228,119 @@ impl DiffLists {
     pub fn iter(&self) -> impl Iterator<Item = &DiffList> {
         self.lists.iter()
     }
+
+    /// Finds the diffs which break the relative order of the offsets around them, writes them to
+    /// a report for review, and optionally removes them.
+    pub fn check_order(
+        &mut self,
+        offset_lists: &OffsetLists,
+        report: &Path,
+        remove: bool,
+    ) -> anyhow::Result<()> {
+        println!("checking diff order...");
+
+        let mut result = String::new();
+        for list in &mut self.lists {
+            let left_offsets = offset_lists.get(list.left).with_context(|| {
+                format!(
+                    "found diff for version '{}', but no corresponding offset info",
+                    list.left
+                )
+            })?;
+            let outliers = list.order_outliers(left_offsets.segments());
+            println!(
+                "\tfound {} outliers in diff '{}' -> '{}'",
+                outliers.len(),
+                list.left,
+                list.right
+            );
+            for i in &outliers {
+                let diff = &list.diffs[*i];
+                let segment = left_offsets
+                    .segments()
+                    .find(diff.left)
+                    .map_or("<none>", |x| x.name.as_str());
+                writeln!(
+                    result,
+                    "{}\t{}\t{}\t{}\t{segment}",
+                    list.left, list.right, diff.left, diff.right
+                )
+                .expect("writing to a string can not fail");
+            }
+            if remove {
+                let mut outliers = outliers.into_iter().peekable();
+                let mut i = 0;
+                list.diffs.retain(|_| {
+                    let outlier = outliers.next_if_eq(&i).is_some();
+                    i += 1;
+                    !outlier
+                });
+            }
+        }
+
+        fs::write(report, result)
+            .with_context(|| format!("failed to write file: {}", report.display()))
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::DiffList;
-    use std::io::BufReader;
+    use super::{
+        longest_increasing_subsequence,
+        Diff,
+        DiffList,
+    };
+    use crate::{
+        common::Offset,
+        errors::ParseErrors,
+        segments::SegmentList,
+    };
+    use std::{
+        io::BufReader,
+        path::Path,
+    };
+
+    #[test]
+    fn test_longest_increasing_subsequence() {
+        let result = longest_increasing_subsequence(&[0x10, 0x20, 0x900, 0x30, 0x40, 0x5, 0x50]);
+        assert_eq!(result, [0, 1, 3, 4, 6]);
+    }
+
+    #[test]
+    fn test_order_outliers() -> anyhow::Result<()> {
+        let mut buffer = BufReader::new(
+            &br"version	1
+segment	140001000	142000000	.text
+"[..],
+        );
+        let segments = SegmentList::parse(
+            &mut buffer,
+            0x1_4000_0000,
+            &mut ParseErrors::default().file(Path::new("test")),
+        )?;
+        // the second and third functions swapped places, while the fifth jumped far ahead
+        let diffs = [
+            (0x1000, 0x1000),
+            (0x1100, 0x1200),
+            (0x1200, 0x1100),
+            (0x1300, 0x1300),
+            (0x1400, 0x1F0_0000),
+            (0x1500, 0x1500),
+        ]
+        .into_iter()
+        .map(|(left, right)| Diff {
+            left: Offset(left),
+            right: Offset(right),
+        })
+        .collect();
+        let list = DiffList {
+            diffs,
+            left: "1.10.130".parse()?,
+            right: "1.10.138".parse()?,
+        };
+        assert_eq!(list.order_outliers(&segments), [4]);
+        Ok(())
+    }
 
     #[test]
     fn test_diffs() -> anyhow::Result<()> {
@@ -180,18 +368,22 @@ Overall success: 18.454%
 0x142E626D8	0x1424D0528
 "[..],
         );
-        let result = DiffList::parse_diffs(&mut buffer)?
-            .iter()
-            .map(|x| (x.left.0, x.right.0))
-            .collect::<Vec<_>>();
+        let result = DiffList::parse_diffs(
+            &mut buffer,
+            0x1_4000_0000,
+            &mut ParseErrors::default().file(Path::new("test")),
+        )?
+        .iter()
+        .map(|x| (x.left.0, x.right.0))
+        .collect::<Vec<_>>();
         assert_eq!(
             result,
             [
-                (0x36C69FE, 0x2C6201E),
-                (0x36C70A4, 0x2C62630),
-                (0x36CAE1D, 0x2C6065D),
-                (0x30C7E4C, 0x272DE5C),
-                (0x2E626D8, 0x24D0528),
+                (0x36C_69FE, 0x2C6_201E),
+                (0x36C_70A4, 0x2C6_2630),
+                (0x36C_AE1D, 0x2C6_065D),
+                (0x30C_7E4C, 0x272_DE5C),
+                (0x2E6_26D8, 0x24D_0528),
             ]
         );
         Ok(())
@@ -0,0 +1,132 @@
+use std::path::{
+    Path,
+    PathBuf,
+};
+
+struct ParseError {
+    path: PathBuf,
+    line: usize,
+    error: anyhow::Error,
+}
+
+/// Decides what happens to lines which fail to parse. When strict, parsing stops at the first such
+/// line. When lenient, such lines are recorded and skipped, until there are more of them than the
+/// maximum.
+#[derive(Default)]
+pub struct ParseErrors {
+    lenient: bool,
+    max_errors: usize,
+    errors: Vec<ParseError>,
+}
+
+impl ParseErrors {
+    pub fn lenient(max_errors: usize) -> Self {
+        Self {
+            lenient: true,
+            max_errors,
+            errors: Vec::new(),
+        }
+    }
+
+    /// Scopes the errors to the given file.
+    pub fn file<'a>(&'a mut self, path: &'a Path) -> FileErrors<'a> {
+        FileErrors { errors: self, path }
+    }
+
+    /// The file and line of every line which was skipped.
+    #[cfg(test)]
+    pub fn lines(&self) -> Vec<(&Path, usize)> {
+        self.errors
+            .iter()
+            .map(|x| (x.path.as_path(), x.line))
+            .collect()
+    }
+
+    /// Prints every line which was skipped.
+    pub fn report(&self) {
+        if !self.lenient {
+            return;
+        }
+        println!("skipped {} lines which failed to parse:", self.errors.len());
+        for error in &self.errors {
+            println!(
+                "\t{}:{}: {:#}",
+                error.path.display(),
+                error.line,
+                error.error
+            );
+        }
+    }
+}
+
+pub struct FileErrors<'a> {
+    errors: &'a mut ParseErrors,
+    path: &'a Path,
+}
+
+impl FileErrors<'_> {
+    /// Checks the result of parsing the given line (counting from 1, or the index of the entry for
+    /// address bins), returning the error when strict, or recording it and returning `None` when
+    /// lenient.
+    pub fn check<T>(
+        &mut self,
+        line: usize,
+        result: anyhow::Result<T>,
+    ) -> anyhow::Result<Option<T>> {
+        match result {
+            Ok(x) => Ok(Some(x)),
+            Err(error) if !self.errors.lenient => {
+                Err(error.context(format!("failed to parse {}:{line}", self.path.display())))
+            }
+            Err(_)
+                if self
+                    .errors
+                    .errors
+                    .iter()
+                    .any(|x| x.path == self.path && x.line == line) =>
+            {
+                // files such as the xrefs are read by more than one pass
+                Ok(None)
+            }
+            Err(error) => {
+                self.errors.errors.push(ParseError {
+                    path: self.path.to_owned(),
+                    line,
+                    error,
+                });
+                if self.errors.errors.len() > self.errors.max_errors {
+                    anyhow::bail!(
+                        "more than {} lines failed to parse, giving up",
+                        self.errors.max_errors
+                    );
+                }
+                Ok(None)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ParseErrors;
+    use std::path::Path;
+
+    #[test]
+    fn test_parse_errors() {
+        let path = Path::new("idaexport_func.txt");
+        let mut strict = ParseErrors::default();
+        assert!(strict.file(path).check(2, anyhow::Ok(1)).unwrap() == Some(1));
+        assert!(strict
+            .file(path)
+            .check::<()>(3, Err(anyhow::anyhow!("bad")))
+            .is_err());
+
+        let mut lenient = ParseErrors::lenient(1);
+        let mut errors = lenient.file(path);
+        assert!(errors
+            .check::<()>(3, Err(anyhow::anyhow!("bad")))
+            .unwrap()
+            .is_none());
+        assert!(errors.check::<()>(4, Err(anyhow::anyhow!("bad"))).is_err());
+    }
+}
@@ -1,7 +1,12 @@
 use crate::{
     addrlib::AddressBins,
-    common::Id,
+    common::{
+        Id,
+        Offset,
+        Version,
+    },
     diffs::DiffLists,
+    offsets::OffsetList,
     OffsetLists,
 };
 use anyhow::Context as _;
@@ -9,15 +14,29 @@ use nonmax::NonMaxU32;
 use petgraph::{
     graph::{
         self,
+        EdgeIndex,
         IndexType,
         NodeIndex,
     },
+    unionfind::UnionFind,
     visit::{
         Bfs,
+        EdgeRef as _,
         IntoNodeIdentifiers as _,
     },
     Undirected,
 };
+use std::{
+    collections::{
+        BTreeMap,
+        HashMap,
+        HashSet,
+        VecDeque,
+    },
+    fmt::Write as _,
+    fs,
+    path::Path,
+};
 
 #[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct Ix(NonMaxU32);
@@ -39,14 +58,128 @@ unsafe impl IndexType for Ix {
     }
 }
 
-type Node = Option<Id>;
+pub struct Node {
+    pub version: Version,
+    pub offset: Offset,
+    pub id: Option<Id>,
+}
+
+/// Identifies which matcher produced an edge.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Source {
+    Diff,
+    Xref,
+    String,
+    Asm,
+    FunctionSize,
+    Interpolation,
+}
+
+impl Source {
+    /// How much an edge from this source can be trusted, relative to the others.
+    pub fn confidence(self) -> u32 {
+        match self {
+            Self::Diff => 8,
+            Self::String | Self::Asm => 6,
+            Self::Xref => 4,
+            Self::FunctionSize => 3,
+            Self::Interpolation => 1,
+        }
+    }
+}
+
+/// The matches between the offsets of two versions, as described by the connected components of
+/// the graph.
+///
+/// An offset is considered matched if its component contains any offset from the other version,
+/// and has a counterpart only if its component contains exactly one offset from each version.
+pub struct Correspondence {
+    left: BTreeMap<Offset, Option<Offset>>,
+    right: BTreeMap<Offset, Option<Offset>>,
+}
+
+impl Correspondence {
+    pub fn left(&self, offset: Offset) -> Option<Offset> {
+        self.left.get(&offset).copied().flatten()
+    }
+
+    pub fn is_left_matched(&self, offset: Offset) -> bool {
+        self.left.contains_key(&offset)
+    }
+
+    pub fn is_right_matched(&self, offset: Offset) -> bool {
+        self.right.contains_key(&offset)
+    }
+
+    /// Iterates over all unique matches, ordered by the left offset.
+    pub fn iter(&self) -> impl Iterator<Item = (Offset, Offset)> + '_ {
+        self.left
+            .iter()
+            .filter_map(|(left, right)| right.map(|right| (*left, right)))
+    }
+}
 
 #[derive(Default)]
-pub struct Graph(graph::Graph<Node, (), Undirected, Ix>);
+pub struct Graph(graph::Graph<Node, Source, Undirected, Ix>);
 
 impl Graph {
-    pub fn add_node(&mut self) -> NodeIndex<Ix> {
-        self.0.add_node(None)
+    pub fn add_node(&mut self, version: Version, offset: Offset) -> NodeIndex<Ix> {
+        self.0.add_node(Node {
+            version,
+            offset,
+            id: None,
+        })
+    }
+
+    pub fn add_edge(&mut self, left: NodeIndex<Ix>, right: NodeIndex<Ix>, source: Source) {
+        self.0.add_edge(left, right, source);
+    }
+
+    /// Whether the graph still has an edge from the given source between the two nodes.
+    pub fn has_edge(&self, left: NodeIndex<Ix>, right: NodeIndex<Ix>, source: Source) -> bool {
+        self.0
+            .edges_connecting(left, right)
+            .any(|x| *x.weight() == source)
+    }
+
+    pub fn correspondence(&self, left: &OffsetList, right: &OffsetList) -> Correspondence {
+        let mut components = UnionFind::new(self.0.node_count());
+        for edge in self.0.edge_references() {
+            components.union(edge.source().index(), edge.target().index());
+        }
+
+        let mut members: HashMap<usize, (Vec<Offset>, Vec<Offset>)> = HashMap::new();
+        for (offset, mapping) in left.iter() {
+            let label = components.find(mapping.ix.index());
+            members.entry(label).or_default().0.push(*offset);
+        }
+        for (offset, mapping) in right.iter() {
+            let label = components.find(mapping.ix.index());
+            members.entry(label).or_default().1.push(*offset);
+        }
+
+        let mut correspondence = Correspondence {
+            left: BTreeMap::new(),
+            right: BTreeMap::new(),
+        };
+        for (lefts, rights) in members.into_values() {
+            if lefts.is_empty() || rights.is_empty() {
+                continue;
+            }
+            let unique = lefts.len() == 1 && rights.len() == 1;
+            for offset in &lefts {
+                correspondence
+                    .left
+                    .insert(*offset, unique.then(|| rights[0]));
+            }
+            for offset in &rights {
+                correspondence
+                    .right
+                    .insert(*offset, unique.then(|| lefts[0]));
+            }
+        }
+
+        correspondence
     }
 
     pub fn add_edges(
@@ -76,13 +209,30 @@ impl Graph {
         for diff_list in diff_lists.iter() {
             let left_offsets = get_offsets!(diff_list.left)?;
             let right_offsets = get_offsets!(diff_list.right)?;
+            let mut rejected = 0usize;
             for diff in diff_list.iter() {
                 if let Some(left_node) = get_ix!(left_offsets, diff.left, diff_list.left) {
                     if let Some(right_node) = get_ix!(right_offsets, diff.right, diff_list.right) {
-                        self.0.add_edge(left_node, right_node, ());
+                        let left_segment = left_offsets.segments().find(diff.left);
+                        let right_segment = right_offsets.segments().find(diff.right);
+                        if let (Some(left_segment), Some(right_segment)) =
+                            (left_segment, right_segment)
+                        {
+                            if left_segment.name != right_segment.name {
+                                rejected += 1;
+                                continue;
+                            }
+                        }
+                        self.add_edge(left_node, right_node, Source::Diff);
                     }
                 }
             }
+            if rejected != 0 {
+                println!(
+                    "\trejected {rejected} cross-segment matches from diff '{}' -> '{}'",
+                    diff_list.left, diff_list.right
+                );
+            }
         }
 
         Ok(())
@@ -105,7 +255,7 @@ impl Graph {
                 if let Some(root_id) = offset_list.get(*offset).map(|x| x.ix) {
                     let mut bfs = Bfs::new(&self.0, root_id);
                     while let Some(node_id) = bfs.next(&self.0) {
-                        let node = &mut self.0[node_id];
+                        let node = &mut self.0[node_id].id;
                         if let Some(id) = node {
                             if id != offset_id {
                                 anyhow::bail!("attempted to assign id '{offset_id}' from bin '{version}' to offset '{offset}', but an id is already assigned ({id})",);
@@ -125,12 +275,12 @@ impl Graph {
         println!("assigning ids to all offsets...");
 
         for node_id in self.0.node_identifiers() {
-            if self.0[node_id].is_none() {
+            if self.0[node_id].id.is_none() {
                 let id = initial_id;
                 initial_id = initial_id.next();
                 let mut bfs = Bfs::new(&self.0, node_id);
                 while let Some(node_id) = bfs.next(&self.0) {
-                    let node = &mut self.0[node_id];
+                    let node = &mut self.0[node_id].id;
                     if node.is_some() {
                         anyhow::bail!(
                             "attempted to assign an id to an offset, but an id is already assigned"
@@ -145,7 +295,227 @@ impl Graph {
         Ok(())
     }
 
+    /// Finds all components which contain more than one offset from the same version, ordered
+    /// from largest to smallest.
+    pub fn find_conflicts(&self) -> Vec<Vec<NodeIndex<Ix>>> {
+        let mut components = UnionFind::new(self.0.node_count());
+        for edge in self.0.edge_references() {
+            components.union(edge.source().index(), edge.target().index());
+        }
+
+        let mut members: HashMap<usize, Vec<NodeIndex<Ix>>> = HashMap::new();
+        for node in self.0.node_indices() {
+            members
+                .entry(components.find(node.index()))
+                .or_default()
+                .push(node);
+        }
+
+        let mut conflicts = members
+            .into_values()
+            .filter(|x| self.has_conflict(x))
+            .collect::<Vec<_>>();
+        conflicts.sort_by(|left, right| right.len().cmp(&left.len()).then(left.cmp(right)));
+        conflicts
+    }
+
+    fn has_conflict(&self, nodes: &[NodeIndex<Ix>]) -> bool {
+        self.conflicting_pair(nodes).is_some()
+    }
+
+    /// Finds two nodes from the same version within the given nodes.
+    fn conflicting_pair(&self, nodes: &[NodeIndex<Ix>]) -> Option<(NodeIndex<Ix>, NodeIndex<Ix>)> {
+        let mut versions = HashMap::new();
+        for node in nodes {
+            if let Some(other) = versions.insert(self.0[*node].version, *node) {
+                return Some((other, *node));
+            }
+        }
+        None
+    }
+
+    pub fn write_conflicts(
+        &self,
+        conflicts: &[Vec<NodeIndex<Ix>>],
+        path: &Path,
+    ) -> anyhow::Result<()> {
+        let mut result = String::new();
+        for (i, nodes) in conflicts.iter().enumerate() {
+            writeln!(result, "component {i}: {} offsets", nodes.len())
+                .expect("writing to a string can not fail");
+            let mut offsets: BTreeMap<Version, Vec<Offset>> = BTreeMap::new();
+            for node in nodes {
+                let node = &self.0[*node];
+                offsets.entry(node.version).or_default().push(node.offset);
+            }
+            for (version, mut offsets) in offsets {
+                offsets.sort();
+                let offsets = offsets
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                writeln!(result, "\t{version}: {offsets}")
+                    .expect("writing to a string can not fail");
+            }
+        }
+        fs::write(path, result).with_context(|| format!("failed to write file: {}", path.display()))
+    }
+
+    /// Splits every conflicting component by repeatedly removing the minimum cut between two of
+    /// its offsets from the same version, weighing edges by their confidence.
+    ///
+    /// Returns the number of edges that were removed.
+    pub fn split_conflicts(&mut self, conflicts: Vec<Vec<NodeIndex<Ix>>>) -> usize {
+        let mut cut = HashSet::new();
+        let mut pending = conflicts;
+        while let Some(nodes) = pending.pop() {
+            let Some((source, sink)) = self.conflicting_pair(&nodes) else {
+                continue;
+            };
+            cut.extend(self.min_cut(&nodes, source, sink, &cut));
+
+            // the cut has split the component in two, so find the pieces and check them again
+            let mut remaining = nodes.iter().copied().collect::<HashSet<_>>();
+            while let Some(root) = remaining.iter().next().copied() {
+                let mut piece = vec![root];
+                remaining.remove(&root);
+                let mut i = 0;
+                while let Some(node) = piece.get(i).copied() {
+                    for edge in self.0.edges(node) {
+                        let next = if edge.source() == node {
+                            edge.target()
+                        } else {
+                            edge.source()
+                        };
+                        if !cut.contains(&edge.id()) && remaining.remove(&next) {
+                            piece.push(next);
+                        }
+                    }
+                    i += 1;
+                }
+                pending.push(piece);
+            }
+        }
+
+        let removed = cut.len();
+        self.0.retain_edges(|_, edge| !cut.contains(&edge));
+        removed
+    }
+
+    /// Finds the edges of the minimum cut separating the source and sink within the given
+    /// component, using the Edmonds-Karp algorithm.
+    fn min_cut(
+        &self,
+        nodes: &[NodeIndex<Ix>],
+        source: NodeIndex<Ix>,
+        sink: NodeIndex<Ix>,
+        ignore: &HashSet<EdgeIndex<Ix>>,
+    ) -> Vec<EdgeIndex<Ix>> {
+        let local = nodes
+            .iter()
+            .enumerate()
+            .map(|(i, x)| (*x, i))
+            .collect::<HashMap<_, _>>();
+        let mut capacity: Vec<HashMap<usize, i64>> = vec![HashMap::new(); nodes.len()];
+        let mut edges = Vec::new();
+        let mut seen = HashSet::new();
+        for node in nodes {
+            for edge in self.0.edges(*node) {
+                if ignore.contains(&edge.id()) || !seen.insert(edge.id()) {
+                    continue;
+                }
+                let (Some(from), Some(to)) = (local.get(&edge.source()), local.get(&edge.target()))
+                else {
+                    continue;
+                };
+                let weight = i64::from(edge.weight().confidence());
+                *capacity[*from].entry(*to).or_default() += weight;
+                *capacity[*to].entry(*from).or_default() += weight;
+                edges.push((edge.id(), *from, *to));
+            }
+        }
+
+        let (source, sink) = (local[&source], local[&sink]);
+        let reachable = loop {
+            let mut previous = vec![None; nodes.len()];
+            let mut queue = VecDeque::from([source]);
+            let mut visited = vec![false; nodes.len()];
+            visited[source] = true;
+            while let Some(node) = queue.pop_front() {
+                for (next, residual) in &capacity[node] {
+                    if *residual > 0 && !visited[*next] {
+                        visited[*next] = true;
+                        previous[*next] = Some(node);
+                        queue.push_back(*next);
+                    }
+                }
+            }
+            if !visited[sink] {
+                break visited;
+            }
+
+            let mut path = Vec::new();
+            let mut node = sink;
+            while let Some(prev) = previous[node] {
+                path.push((prev, node));
+                node = prev;
+            }
+            let flow = path
+                .iter()
+                .map(|(from, to)| capacity[*from][to])
+                .min()
+                .unwrap_or_default();
+            for (from, to) in path {
+                *capacity[from].entry(to).or_default() -= flow;
+                *capacity[to].entry(from).or_default() += flow;
+            }
+        };
+
+        edges
+            .into_iter()
+            .filter(|(_, from, to)| reachable[*from] != reachable[*to])
+            .map(|(edge, _, _)| edge)
+            .collect()
+    }
+
     pub fn get(&self, key: NodeIndex<Ix>) -> Id {
-        self.0[key].expect("expected id to already be initialized upon access")
+        self.0[key]
+            .id
+            .expect("expected id to already be initialized upon access")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Graph,
+        Source,
+    };
+    use crate::common::{
+        Offset,
+        Version,
+    };
+
+    #[test]
+    fn test_split_conflicts() -> anyhow::Result<()> {
+        let left: Version = "1.10.130".parse()?;
+        let right: Version = "1.10.138".parse()?;
+        let mut graph = Graph::default();
+        let a = graph.add_node(left, Offset(0x1000));
+        let b = graph.add_node(right, Offset(0x1010));
+        let c = graph.add_node(left, Offset(0x1020));
+        let d = graph.add_node(right, Offset(0x1030));
+        graph.add_edge(a, b, Source::Diff);
+        graph.add_edge(b, c, Source::Interpolation);
+        graph.add_edge(c, d, Source::Diff);
+
+        let conflicts = graph.find_conflicts();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].len(), 4);
+        assert_eq!(graph.split_conflicts(conflicts), 1);
+        assert!(graph.find_conflicts().is_empty());
+        assert!(graph.0.find_edge(b, c).is_none());
+        Ok(())
     }
 }
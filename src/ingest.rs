@@ -0,0 +1,225 @@
+use crate::{
+    common::Version,
+    manifest,
+    pe::Executable,
+};
+use anyhow::Context as _;
+use regex_lite::Regex;
+use std::{
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+/// The name of the directory holding the idaexport files of the given version, e.g. `1.10.163`.
+fn directory_name(version: Version) -> anyhow::Result<String> {
+    if version[3] != 0 {
+        anyhow::bail!(
+            "version '{version}' has a build number, which can not be represented by a directory name"
+        );
+    }
+    Ok(format!("{}.{}.{}", version[0], version[1], version[2]))
+}
+
+/// The prefix of the names of the given module, which must be a name that is located again when
+/// generating.
+fn prefix(module: &str) -> anyhow::Result<String> {
+    let pattern = Regex::new(&format!("^{}$", manifest::MODULE_PATTERN))
+        .context("failed to build module pattern")?;
+    let prefix = manifest::module_prefix(module);
+    if !pattern.is_match(&prefix) {
+        anyhow::bail!("module '{module}' must start with a letter and hold only word characters");
+    }
+    Ok(prefix)
+}
+
+/// Copies the given files, skipping those whose destination already holds the same content.
+/// Nothing is copied if the destination of any file holds different content.
+fn copy_all(files: &[(PathBuf, PathBuf)]) -> anyhow::Result<()> {
+    let mut pending = Vec::new();
+    for (source, destination) in files {
+        if destination.exists() {
+            let old = fs::read(destination)
+                .with_context(|| format!("failed to read file: {}", destination.display()))?;
+            let new = fs::read(source)
+                .with_context(|| format!("failed to read file: {}", source.display()))?;
+            if old != new {
+                anyhow::bail!(
+                    "refusing to overwrite file with differing content: {destination:?} (from {source:?})"
+                );
+            }
+            println!("\t{} is already up to date", destination.display());
+        } else {
+            pending.push((source, destination));
+        }
+    }
+
+    for (source, destination) in pending {
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create directory: {}", parent.display()))?;
+        }
+        fs::copy(source, destination).with_context(|| {
+            format!(
+                "failed to copy file: {} to {}",
+                source.display(),
+                destination.display()
+            )
+        })?;
+        println!("\tcopied {} to {}", source.display(), destination.display());
+    }
+    Ok(())
+}
+
+/// Copies an idaexport directory, and optionally the executable it was exported from, into the
+/// directory named after its module and version within the root directory. The version is read
+/// from the executable, and a given version must agree with it.
+pub fn ingest_idaexport(
+    root_dir: &Path,
+    idaexport: &Path,
+    executable: Option<&Path>,
+    version: Option<Version>,
+    module: &str,
+) -> anyhow::Result<()> {
+    println!("ingesting idaexport...");
+
+    let prefix = prefix(module)?;
+    let executable = match executable {
+        Some(x) => Some(x.to_owned()),
+        None => Executable::find(idaexport)?,
+    };
+    let found = executable
+        .as_deref()
+        .map(|path| {
+            Executable::load(path)?.version().with_context(|| {
+                format!("failed to read version from executable: {}", path.display())
+            })
+        })
+        .transpose()?;
+    let version = match (version, found) {
+        (Some(given), Some(found)) if given != found => anyhow::bail!(
+            "executable has version '{found}', but version '{given}' was given: {:?}",
+            executable.unwrap_or_default()
+        ),
+        (Some(x), _) | (None, Some(x)) => x,
+        (None, None) => anyhow::bail!(
+            "found no executable to read the version from, please specify one: {idaexport:?}"
+        ),
+    };
+    let destination = root_dir.join(prefix + &directory_name(version)?);
+
+    let mut files = Vec::new();
+    for entry in fs::read_dir(idaexport)
+        .with_context(|| format!("failed to read directory: {}", idaexport.display()))?
+    {
+        let path = entry
+            .with_context(|| format!("error while reading directory: {}", idaexport.display()))?
+            .path();
+        if path.is_file() {
+            if let Some(file_name) = path.file_name() {
+                files.push((path.clone(), destination.join(file_name)));
+            }
+        }
+    }
+    if let Some(path) = executable {
+        if let Some(file_name) = path.file_name() {
+            let target = destination.join(file_name);
+            if !files.iter().any(|(_, x)| *x == target) {
+                files.push((path.clone(), target));
+            }
+        }
+    }
+
+    copy_all(&files)
+}
+
+/// Copies a diff report into the root directory, named after the module and the versions of its
+/// left and right columns.
+pub fn ingest_diff(
+    root_dir: &Path,
+    diff: &Path,
+    left: Version,
+    right: Version,
+    module: &str,
+) -> anyhow::Result<()> {
+    println!("ingesting diff...");
+
+    let file_name = format!(
+        "{}{}_{}.txt",
+        prefix(module)?,
+        directory_name(left)?,
+        directory_name(right)?
+    );
+    copy_all(&[(diff.to_owned(), root_dir.join(file_name))])
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        common,
+        pe::Executable,
+    };
+    use std::fs;
+
+    #[test]
+    fn test_copy_all() -> anyhow::Result<()> {
+        let root = common::test_dir("ingest-copy");
+        let sources = ["a.txt", "b.txt", "c.txt"].map(|x| root.join(x));
+        let destinations = ["a.txt", "b.txt", "c.txt"].map(|x| root.join("out").join(x));
+        for (source, content) in sources.iter().zip(["a", "b", "c"]) {
+            fs::write(source, content)?;
+        }
+        let files = sources
+            .iter()
+            .cloned()
+            .zip(destinations.iter().cloned())
+            .collect::<Vec<_>>();
+        super::copy_all(&files[..2])?;
+        assert_eq!(fs::read_to_string(&destinations[1])?, "b");
+
+        // copying the same content again is fine, but differing content stops the whole copy
+        super::copy_all(&files[..2])?;
+        fs::write(&sources[1], "changed")?;
+        assert!(super::copy_all(&files).is_err());
+        assert!(!destinations[2].exists());
+        assert_eq!(fs::read_to_string(&destinations[1])?, "b");
+        fs::remove_dir_all(root)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_ingest_idaexport() -> anyhow::Result<()> {
+        let root = common::test_dir("ingest-idaexport");
+        let idaexport = root.join("export");
+        fs::create_dir_all(&idaexport)?;
+        fs::write(idaexport.join("idaexport_base.txt"), "version\t1\n")?;
+        fs::write(idaexport.join("Fallout4.exe"), Executable::sample_bytes())?;
+
+        let result =
+            super::ingest_idaexport(&root, &idaexport, None, Some("1.10.162".parse()?), "");
+        assert!(result.is_err());
+        assert!(!root.join("1.10.162").exists());
+
+        super::ingest_idaexport(&root, &idaexport, None, None, "")?;
+        let destination = root.join("1.10.163");
+        assert!(destination.join("idaexport_base.txt").exists());
+        assert!(destination.join("Fallout4.exe").exists());
+
+        super::ingest_idaexport(&root, &idaexport, None, None, "CreationKit")?;
+        assert!(root
+            .join("CreationKit-1.10.163")
+            .join("Fallout4.exe")
+            .exists());
+        assert!(super::ingest_idaexport(&root, &idaexport, None, None, "Creation Kit").is_err());
+
+        let diff = root.join("diff.txt");
+        fs::write(&diff, "")?;
+        let (left, right) = ("1.10.130".parse()?, "1.10.138".parse()?);
+        super::ingest_diff(&root, &diff, left, right, "CreationKit")?;
+        assert!(root.join("CreationKit-1.10.130_1.10.138.txt").exists());
+        fs::remove_dir_all(root)?;
+        Ok(())
+    }
+}
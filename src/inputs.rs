@@ -0,0 +1,324 @@
+use crate::{
+    addrlib::{
+        self,
+        BinFormat,
+    },
+    common::Version,
+    manifest::{
+        self,
+        Manifest,
+    },
+    pe::{
+        self,
+        Executable,
+    },
+    profile::Profile,
+};
+use anyhow::Context as _;
+use regex_lite::Regex;
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{
+        BufRead as _,
+        BufReader,
+    },
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+use walkdir::WalkDir;
+
+const IDAEXPORT_FILES: [&str; 9] = [
+    "idaexport_asm.txt",
+    "idaexport_base.txt",
+    "idaexport_func.txt",
+    "idaexport_global.txt",
+    "idaexport_name.txt",
+    "idaexport_segment.txt",
+    "idaexport_string.txt",
+    "idaexport_vtable.txt",
+    "idaexport_xrefs.txt",
+];
+
+/// Reads the `version` header of an idaexport file, returning a description of the problem if it
+/// is not supported.
+fn check_header(path: &Path) -> anyhow::Result<Option<String>> {
+    let file =
+        File::open(path).with_context(|| format!("failed to open file: {}", path.display()))?;
+    let mut line = String::new();
+    BufReader::new(file)
+        .read_line(&mut line)
+        .with_context(|| format!("failed to read file: {}", path.display()))?;
+    Ok(match line.trim_end().strip_prefix("version\t") {
+        Some("1") => None,
+        Some(version) => Some(format!(
+            "unsupported version header '{version}': {}",
+            path.display()
+        )),
+        None => Some(format!("missing version header: {}", path.display())),
+    })
+}
+
+/// Checks the contents of an idaexport directory of the given version. Directories holding none of
+/// the idaexport files are fine, so long as they hold an executable to build the offsets from
+/// instead. The executable must have the same version as the directory.
+fn check_idaexport(
+    directory: &Path,
+    version: Version,
+    problems: &mut Vec<String>,
+) -> anyhow::Result<()> {
+    let present = IDAEXPORT_FILES
+        .iter()
+        .filter(|x| directory.join(x).exists())
+        .collect::<Vec<_>>();
+    let executable = Executable::find(directory)?;
+    if !present.is_empty() || executable.is_none() {
+        for file_name in IDAEXPORT_FILES.iter().filter(|x| !present.contains(x)) {
+            problems.push(format!(
+                "missing {file_name} in directory: {}",
+                directory.display()
+            ));
+        }
+    }
+    for file_name in present {
+        problems.extend(check_header(&directory.join(file_name))?);
+    }
+    if let Some(path) = &executable {
+        let checked = Executable::load(path).and_then(|x| pe::check_version(&x, path, version));
+        if let Err(err) = checked {
+            problems.push(format!("{err:#}"));
+        }
+    }
+
+    for entry in directory
+        .read_dir()
+        .with_context(|| format!("failed to read directory: {}", directory.display()))?
+    {
+        let path = entry
+            .with_context(|| format!("error while reading directory: {}", directory.display()))?
+            .path();
+        let expected = path
+            .file_name()
+            .and_then(|x| x.to_str())
+            .is_some_and(|x| IDAEXPORT_FILES.contains(&x));
+        if path.is_file() && !expected && Some(&path) != executable.as_ref() {
+            problems.push(format!("file matches no known name: {}", path.display()));
+        }
+    }
+    Ok(())
+}
+
+/// Checks the inputs listed by `libgen.toml`, which must all exist.
+fn check_manifest(
+    root_dir: &Path,
+    profile: Option<Profile>,
+    problems: &mut Vec<String>,
+) -> anyhow::Result<()> {
+    let manifest = Manifest::load(root_dir, profile)?;
+    for (version, directory) in manifest.idaexports() {
+        if directory.is_dir() {
+            check_idaexport(directory, version, problems)?;
+        } else {
+            problems.push(format!(
+                "found no idaexport directory for version '{version}': {}",
+                directory.display()
+            ));
+        }
+    }
+    for diff in manifest.diffs() {
+        if !diff.path.is_file() {
+            problems.push(format!(
+                "found no diff for '{}' -> '{}': {}",
+                diff.left,
+                diff.right,
+                diff.path.display()
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Walks the artifact directory, and reports anything which would make generation fail or which
+/// would be silently ignored by it. When the inputs are listed by `libgen.toml`, only the listed
+/// inputs are checked. Otherwise, bins are expected to be named as the given profile names them.
+pub fn check_inputs(root_dir: &Path, profile: Option<Profile>) -> anyhow::Result<()> {
+    println!("checking inputs...");
+    report(&find_problems(root_dir, profile)?)
+}
+
+fn find_problems(root_dir: &Path, profile: Option<Profile>) -> anyhow::Result<Vec<String>> {
+    let mut problems = Vec::new();
+    if root_dir.join(manifest::FILE_NAME).exists() {
+        check_manifest(root_dir, profile, &mut problems)?;
+        return Ok(problems);
+    }
+
+    let module_pattern = manifest::MODULE_PATTERN;
+    let dir_pattern = Regex::new(&format!(r"^{module_pattern}(\d+)\.(\d+)\.(\d+)$"))
+        .context("failed to build directory pattern")?;
+    let diff_pattern = Regex::new(&format!(
+        r"^{module_pattern}(\d+)\.(\d+)\.(\d+)_(\d+)\.(\d+)\.(\d+)\.txt$"
+    ))
+    .context("failed to build diff pattern")?;
+    let bin_pattern = addrlib::file_name_pattern(profile.unwrap_or_default(), BinFormat::Binary)?;
+    let report_pattern = Regex::new(
+        r"^(?:[A-Za-z]\w*-)?((order-outliers|conflicts|stats|lifecycle)|(removed|release-notes|signatures|verify|version)-\d+-\d+-\d+-\d+)\.txt$",
+    )
+    .context("failed to build report pattern")?;
+
+    let module =
+        |captures: &regex_lite::Captures| captures.get(1).map_or("", |x| x.as_str()).to_owned();
+    let mut idaexports: BTreeMap<(String, Version), Vec<PathBuf>> = BTreeMap::new();
+    let mut diffs: BTreeMap<(String, Version, Version), Vec<PathBuf>> = BTreeMap::new();
+    let mut bins: BTreeMap<(String, Version), Vec<PathBuf>> = BTreeMap::new();
+    for dir_entry in WalkDir::new(root_dir).min_depth(1) {
+        let dir_entry = dir_entry
+            .with_context(|| format!("error while walking directory: {}", root_dir.display()))?;
+        let path = dir_entry.path();
+        let Some(file_name) = path.file_name().and_then(|x| x.to_str()) else {
+            problems.push(format!(
+                "file name is not valid unicode: {}",
+                path.display()
+            ));
+            continue;
+        };
+
+        if dir_entry.file_type().is_dir() {
+            if let Some(captures) = dir_pattern.captures(file_name) {
+                let version = (&captures[2], &captures[3], &captures[4]).try_into()?;
+                idaexports
+                    .entry((module(&captures), version))
+                    .or_default()
+                    .push(path.to_owned());
+                check_idaexport(path, version, &mut problems)?;
+            }
+        } else if path
+            .parent()
+            .and_then(|x| x.file_name())
+            .and_then(|x| x.to_str())
+            .is_some_and(|x| dir_pattern.is_match(x))
+        {
+            // checked along with the directory itself
+        } else if let Some(key) = addrlib::parse_file_name(&bin_pattern, path)? {
+            bins.entry(key).or_default().push(path.to_owned());
+        } else if let Some(captures) = diff_pattern.captures(file_name) {
+            let left = (&captures[2], &captures[3], &captures[4]).try_into()?;
+            let right = (&captures[5], &captures[6], &captures[7]).try_into()?;
+            diffs
+                .entry((module(&captures), left, right))
+                .or_default()
+                .push(path.to_owned());
+        } else if !report_pattern.is_match(file_name) {
+            problems.push(format!("file matches no known name: {}", path.display()));
+        }
+    }
+
+    check_located(&idaexports, &diffs, &bins, &mut problems);
+    Ok(problems)
+}
+
+/// Checks that every input located by its name is unique within its module, and that every diff
+/// and bin has an idaexport directory to go with it.
+fn check_located(
+    idaexports: &BTreeMap<(String, Version), Vec<PathBuf>>,
+    diffs: &BTreeMap<(String, Version, Version), Vec<PathBuf>>,
+    bins: &BTreeMap<(String, Version), Vec<PathBuf>>,
+    problems: &mut Vec<String>,
+) {
+    for ((module, version), paths) in idaexports {
+        let version = format!("{}{version}", manifest::module_prefix(module));
+        if paths.len() > 1 {
+            problems.push(format!(
+                "found version '{version}' in more than one directory: {paths:?}"
+            ));
+        }
+    }
+    for ((module, left, right), paths) in diffs {
+        let prefix = manifest::module_prefix(module);
+        if paths.len() > 1 {
+            problems.push(format!(
+                "found diff '{prefix}{left}' -> '{prefix}{right}' more than once: {paths:?}"
+            ));
+        }
+        for version in [left, right] {
+            if !idaexports.contains_key(&(module.clone(), *version)) {
+                problems.push(format!(
+                    "found diff for version '{prefix}{version}', but no idaexport directory: {paths:?}"
+                ));
+            }
+        }
+    }
+    for (key, paths) in bins {
+        let version = format!("{}{}", manifest::module_prefix(&key.0), key.1);
+        if paths.len() > 1 {
+            problems.push(format!(
+                "found bin for version '{version}' more than once: {paths:?}"
+            ));
+        }
+        if !idaexports.contains_key(key) {
+            problems.push(format!(
+                "found bin for version '{version}', but no idaexport directory: {paths:?}"
+            ));
+        }
+    }
+}
+
+fn report(problems: &[String]) -> anyhow::Result<()> {
+    for problem in problems {
+        println!("\t{problem}");
+    }
+    if !problems.is_empty() {
+        anyhow::bail!("found {} problems with the inputs", problems.len());
+    }
+    println!("\tfound no problems");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common;
+    use std::fs;
+
+    #[test]
+    fn test_modules() -> anyhow::Result<()> {
+        let root = common::test_dir("check-modules");
+        common::write_idaexport(&root.join("1.10.138"), &[], &[], &[])?;
+        common::write_idaexport(&root.join("CreationKit-1.10.130"), &[], &[], &[])?;
+        for file in [
+            "version-1-10-138-0.bin",
+            "CreationKit-version-1-10-130-0.bin",
+            "CreationKit-version-1-10-138-0.bin",
+            "CreationKit-stats.txt",
+        ] {
+            fs::write(root.join(file), "")?;
+        }
+
+        let problems = super::find_problems(&root, None)?;
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].starts_with("found bin for version 'CreationKit-v1.10.138.0'"));
+        fs::remove_dir_all(root)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_located() -> anyhow::Result<()> {
+        let root = common::test_dir("check-located");
+        common::write_idaexport(&root.join("1.10.130"), &[], &[], &[])?;
+        common::write_idaexport(&root.join("old").join("1.10.130"), &[], &[], &[])?;
+        for file in ["1.10.130_1.10.138.txt", "version-1-10-138-0.bin"] {
+            fs::write(root.join(file), "")?;
+        }
+
+        let problems = super::find_problems(&root, None)?;
+        assert_eq!(problems.len(), 3);
+        assert!(problems[0].starts_with("found version 'v1.10.130.0' in more than one directory"));
+        assert!(problems[1]
+            .starts_with("found diff for version 'v1.10.138.0', but no idaexport directory"));
+        assert!(problems[2]
+            .starts_with("found bin for version 'v1.10.138.0', but no idaexport directory"));
+        fs::remove_dir_all(root)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,149 @@
+use crate::{
+    graph::{
+        Graph,
+        Source,
+    },
+    offsets::OffsetLists,
+};
+use std::ops::Bound;
+
+/// The segments where the layout of data between two matched offsets is expected to be preserved.
+const SEGMENTS: [&str; 2] = [".rdata", ".data"];
+
+/// Matches offsets lying between two matched neighbours by their relative position, whenever both
+/// neighbours are the same distance apart in each version.
+///
+/// These matches are only a guess, and so are added as low confidence edges.
+pub fn interpolate(graph: &mut Graph, offset_lists: &OffsetLists) {
+    println!("interpolating matches between matched neighbours...");
+
+    for ((left_version, left_offsets), (right_version, right_offsets)) in
+        offset_lists.adjacent_pairs()
+    {
+        let correspondence = graph.correspondence(left_offsets, right_offsets);
+        let anchors = correspondence
+            .iter()
+            .filter(|(left, right)| {
+                let left = left_offsets.segments().find(*left);
+                let right = right_offsets.segments().find(*right);
+                match (left, right) {
+                    (Some(left), Some(right)) => {
+                        left.name == right.name && SEGMENTS.contains(&left.name.as_str())
+                    }
+                    _ => false,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let mut edges = Vec::new();
+        for window in anchors.windows(2) {
+            let [(previous_left, previous_right), (next_left, next_right)] = window else {
+                continue;
+            };
+            if next_right <= previous_right
+                || next_left.0 - previous_left.0 != next_right.0 - previous_right.0
+            {
+                continue;
+            }
+            let left_segment = left_offsets.segments().find(*previous_left);
+            if left_segment.map(|x| &x.name)
+                != left_offsets.segments().find(*next_left).map(|x| &x.name)
+            {
+                continue;
+            }
+
+            let range = (Bound::Excluded(*previous_left), Bound::Excluded(*next_left));
+            for (left, left_mapping) in left_offsets.range(range) {
+                if correspondence.is_left_matched(*left) {
+                    continue;
+                }
+                let mut right = *previous_right;
+                right.0 += left.0 - previous_left.0;
+                if correspondence.is_right_matched(right) {
+                    continue;
+                }
+                if let Some(right_mapping) = right_offsets.get(right) {
+                    edges.push((left_mapping.ix, right_mapping.ix));
+                }
+            }
+        }
+
+        for (left, right) in &edges {
+            graph.add_edge(*left, *right, Source::Interpolation);
+        }
+        println!(
+            "\t'{left_version}' -> '{right_version}': {} offsets",
+            edges.len()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        common::{
+            self,
+            Offset,
+        },
+        errors::ParseErrors,
+        graph::Source,
+        manifest::Manifest,
+        offsets::OffsetLists,
+    };
+    use std::fs;
+
+    #[test]
+    fn test_interpolate() -> anyhow::Result<()> {
+        let root = common::test_dir("interpolate");
+        // the last anchors are further apart on the right, so the global between them is left alone
+        common::write_idaexport(
+            &root.join("1.10.130"),
+            &[],
+            &[0x3000, 0x3008, 0x3010, 0x3100, 0x3108, 0x3110],
+            &[],
+        )?;
+        common::write_idaexport(
+            &root.join("1.10.138"),
+            &[],
+            &[0x3020, 0x3028, 0x3030, 0x3120, 0x3128, 0x3138],
+            &[],
+        )?;
+
+        let manifest = Manifest::load(&root, None)?;
+        let (offset_lists, mut graph) =
+            OffsetLists::parse_all(&manifest, &[], &mut ParseErrors::default())?;
+        let ((_, left), (_, right)) = offset_lists
+            .adjacent_pairs()
+            .next()
+            .expect("expected two versions");
+        for (x, y) in [
+            (0x3000, 0x3020),
+            (0x3010, 0x3030),
+            (0x3100, 0x3120),
+            (0x3110, 0x3138),
+        ] {
+            let x = left.get(Offset(x)).expect("expected left global").ix;
+            let y = right.get(Offset(y)).expect("expected right global").ix;
+            graph.add_edge(x, y, Source::Diff);
+        }
+        super::interpolate(&mut graph, &offset_lists);
+
+        let result = graph
+            .correspondence(left, right)
+            .iter()
+            .map(|(x, y)| (x.0, y.0))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            result,
+            [
+                (0x3000, 0x3020),
+                (0x3008, 0x3028),
+                (0x3010, 0x3030),
+                (0x3100, 0x3120),
+                (0x3110, 0x3138),
+            ]
+        );
+        fs::remove_dir_all(root)?;
+        Ok(())
+    }
+}
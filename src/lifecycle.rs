@@ -0,0 +1,148 @@
+use crate::{
+    addrlib::AddressBins,
+    common::{
+        Id,
+        Version,
+    },
+    errors::ParseErrors,
+    manifest::{
+        self,
+        Manifest,
+    },
+    profile::Profile,
+};
+use anyhow::Context as _;
+use std::{
+    collections::{
+        BTreeMap,
+        BTreeSet,
+    },
+    fmt::Write as _,
+    fs,
+    path::Path,
+};
+
+/// The versions in which an id is present, out of every version with an address bin.
+struct Lifecycle {
+    first: Version,
+    last: Version,
+    missing: Vec<Version>,
+}
+
+fn lifecycles(versions: &BTreeMap<Version, BTreeSet<Id>>) -> BTreeMap<Id, Lifecycle> {
+    let mut result: BTreeMap<Id, Lifecycle> = BTreeMap::new();
+    for (version, ids) in versions {
+        for id in ids {
+            result
+                .entry(*id)
+                .and_modify(|x| x.last = *version)
+                .or_insert(Lifecycle {
+                    first: *version,
+                    last: *version,
+                    missing: Vec::new(),
+                });
+        }
+    }
+
+    for (id, lifecycle) in &mut result {
+        lifecycle.missing = versions
+            .range(lifecycle.first..=lifecycle.last)
+            .filter(|(_, ids)| !ids.contains(id))
+            .map(|(version, _)| *version)
+            .collect();
+    }
+
+    result
+}
+
+/// Writes the first and last version in which every id of the given module is present, along with
+/// the versions in between that are missing it, to `lifecycle.txt`. The ids present in the second
+/// newest version but not in the newest are written to `removed-<version>.txt`. Both are prefixed
+/// by the module, if it is not the game itself.
+pub fn write_lifecycle(
+    root_dir: &Path,
+    profile: Option<Profile>,
+    module: &str,
+) -> anyhow::Result<()> {
+    let manifest = Manifest::load(root_dir, profile)?.module(module);
+    let address_bins = AddressBins::parse_all(&manifest, &mut ParseErrors::default())
+        .context("failed to parse address bins")?;
+    let versions = address_bins
+        .iter()
+        .map(|(version, bin)| (*version, bin.iter().map(|x| x.0).collect::<BTreeSet<_>>()))
+        .collect::<BTreeMap<_, _>>();
+
+    println!("writing lifecycle...");
+    let mut result = String::new();
+    for (id, lifecycle) in lifecycles(&versions) {
+        let missing = lifecycle
+            .missing
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        writeln!(
+            result,
+            "{id}\t{}\t{}\t{}",
+            lifecycle.first,
+            lifecycle.last,
+            missing.join(",")
+        )
+        .expect("writing to a string can not fail");
+    }
+    let path = root_dir.join(manifest::module_prefix(module) + "lifecycle.txt");
+    fs::write(&path, result)
+        .with_context(|| format!("failed to write file: {}", path.display()))?;
+
+    let mut newest = versions.iter().rev();
+    if let (Some((version, ids)), Some((_, previous))) = (newest.next(), newest.next()) {
+        let removed = previous.difference(ids).collect::<Vec<_>>();
+        println!("\t{} ids were removed in {version}", removed.len());
+        let mut result = String::new();
+        for id in removed {
+            writeln!(result, "{id}").expect("writing to a string can not fail");
+        }
+        let path = root_dir.join(manifest::report_name(module, "removed", *version));
+        fs::write(&path, result)
+            .with_context(|| format!("failed to write file: {}", path.display()))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{
+        Id,
+        Version,
+    };
+    use std::collections::{
+        BTreeMap,
+        BTreeSet,
+    };
+
+    #[test]
+    fn test_lifecycles() -> anyhow::Result<()> {
+        let id = |x: u64| Id::try_from(x);
+        let versions = BTreeMap::from([
+            (
+                "1.10.130".parse::<Version>()?,
+                BTreeSet::from([id(1)?, id(2)?]),
+            ),
+            ("1.10.138".parse()?, BTreeSet::from([id(2)?, id(3)?])),
+            ("1.10.162".parse()?, BTreeSet::from([id(1)?, id(3)?])),
+        ]);
+        let lifecycles = super::lifecycles(&versions);
+
+        let first = &lifecycles[&id(1)?];
+        assert!(first.first == "1.10.130".parse()?);
+        assert!(first.last == "1.10.162".parse()?);
+        assert!(first.missing == ["1.10.138".parse()?]);
+
+        let second = &lifecycles[&id(2)?];
+        assert!(second.first == "1.10.130".parse()?);
+        assert!(second.last == "1.10.138".parse()?);
+        assert!(second.missing.is_empty());
+
+        Ok(())
+    }
+}
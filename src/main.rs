@@ -2,17 +2,51 @@
 #![allow(clippy::redundant_else)]
 
 mod addrlib;
+mod asm;
+mod bindiff;
+mod boundaries;
 mod common;
 mod diffs;
+mod errors;
 mod graph;
+mod ingest;
+mod inputs;
+mod interpolation;
+mod lifecycle;
+mod manifest;
 mod offsets;
+mod pe;
+mod profile;
+mod releasenotes;
+mod segments;
+mod signatures;
+mod stats;
+mod strings;
+mod versionlib;
+mod xrefs;
 
-use addrlib::AddressBins;
+use addrlib::{
+    AddressBins,
+    BinFormat,
+};
 use anyhow::Context as _;
-use clap::Parser;
+use clap::{
+    Args,
+    Parser,
+    Subcommand,
+    ValueEnum,
+};
+use common::Version;
 use diffs::DiffLists;
+use errors::ParseErrors;
+use manifest::Manifest;
 use offsets::OffsetLists;
-use std::path::PathBuf;
+use profile::Profile;
+use serde::Deserialize;
+use std::path::{
+    Path,
+    PathBuf,
+};
 
 fn input_directory_validator(input_directory: &str) -> Result<PathBuf, &'static str> {
     let input_directory: PathBuf = input_directory.into();
@@ -26,26 +60,589 @@ fn input_directory_validator(input_directory: &str) -> Result<PathBuf, &'static
 }
 
 #[derive(Parser)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    generate: GenerateArgs,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Generate a unique byte signature for every function id of a version
+    Signatures(SignaturesArgs),
+
+    /// List the first and last version in which every id is present, and the ids removed in the
+    /// newest version
+    Lifecycle(LifecycleArgs),
+
+    /// Summarize the changes between the newest address bin and the one before it
+    ReleaseNotes(ReleaseNotesArgs),
+
+    /// Compare two address bins, listing added and removed ids, and ids whose offset changed
+    DiffBins(DiffBinsArgs),
+
+    /// Dump an address bin as diff-friendly text, one line per entry sorted by id
+    Bin2txt(Bin2txtArgs),
+
+    /// Rebuild an address bin from the text written by `bin2txt`
+    Txt2bin(Txt2binArgs),
+
+    /// Check address bins for unsorted or duplicate entries, and a len that does not match the
+    /// file
+    ValidateBin(ValidateBinArgs),
+
+    /// Check the address bin of a version against the executable of that version
+    VerifyExe(VerifyExeArgs),
+
+    /// Print the file version of an executable
+    ExeVersion(ExeVersionArgs),
+
+    /// Copy an idaexport directory and/or a diff report into the expected layout of the artifact
+    /// directory
+    Ingest(IngestArgs),
+
+    /// Check the layout of the artifact directory before generating
+    CheckInputs(CheckInputsArgs),
+}
+
+#[derive(Clone, Copy, Default, Deserialize, PartialEq, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+enum OrderOutliers {
+    /// Do not check diffs for order outliers
+    #[default]
+    Ignore,
+
+    /// Write order outliers to `order-outliers.txt` in the input directory
+    Report,
+
+    /// Report order outliers, and remove them before they are added to the graph
+    Drop,
+}
+
+#[derive(Clone, Copy, Default, Deserialize, PartialEq, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+enum Conflicts {
+    /// Do not check the graph for conflicts
+    #[default]
+    Ignore,
+
+    /// Write components containing more than one offset from the same version to `conflicts.txt`
+    /// in the input directory
+    Report,
+
+    /// Report conflicts, and split them by removing the minimum cut between conflicting offsets
+    Split,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, ValueEnum)]
+enum Format {
+    #[default]
+    Text,
+    Json,
+}
+
+/// Generates address bins for all versions which are missing them. Settings which are not given
+/// are taken from the `generate` table of `libgen.toml`, if there is one, and toggles can be turned
+/// off from the command line with `=false` (e.g. `--match-xrefs=false`).
+#[derive(Args, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+struct GenerateArgs {
+    #[arg(value_parser = input_directory_validator)]
+    #[serde(skip)]
+    input_directory: Option<PathBuf>,
+
+    /// Only assign ids to offsets within the given segments (e.g. `.text,.rdata,.data`)
+    #[arg(long, value_delimiter = ',')]
+    sections: Vec<String>,
+
+    /// How to handle diffs which break the relative order of matches within a segment, defaults
+    /// to `ignore`
+    #[arg(long, value_enum)]
+    order_outliers: Option<OrderOutliers>,
+
+    /// How to handle components which contain more than one offset from the same version,
+    /// defaults to `ignore`
+    #[arg(long, value_enum)]
+    conflicts: Option<Conflicts>,
+
+    /// Match offsets referenced from the same position within already matched functions
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    match_xrefs: Option<bool>,
+
+    /// Match functions which are the sole reference to a string literal that is unique in both
+    /// versions
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    match_strings: Option<bool>,
+
+    /// Match functions whose normalized instructions from `idaexport_asm.txt` hash to a value
+    /// that is unique within both versions
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    match_asm: Option<bool>,
+
+    /// Match functions lying between two matched functions when the sizes of the functions in
+    /// between are identical in both versions
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    match_function_sizes: Option<bool>,
+
+    /// Match unmatched offsets in `.rdata` and `.data` by their position relative to two matched
+    /// neighbours that are the same distance apart in both versions (low confidence)
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    interpolate: Option<bool>,
+
+    /// Write where the ids of every version came from, and how many edges each diff contributed,
+    /// to `stats.txt` in the input directory
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    stats: Option<bool>,
+
+    /// Rewrite existing address bins to also include any offsets they are missing, failing if an
+    /// existing mapping would change
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    extend: Option<bool>,
+
+    /// Skip lines which fail to parse instead of stopping at the first, and report all of them at
+    /// the end. Can not be combined with `--extend`, which would drop skipped entries from the bins
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    lenient: Option<bool>,
+
+    /// Give up once more than this many lines have failed to parse in lenient mode, defaults to
+    /// 1000
+    #[arg(long)]
+    max_errors: Option<usize>,
+
+    /// How address bins are read and written, defaults to `binary`
+    #[arg(long, value_enum)]
+    bin_format: Option<BinFormat>,
+
+    /// The game to generate address bins for, which decides their names and layout, defaults to
+    /// `fallout4`
+    #[arg(long, value_enum)]
+    profile: Option<Profile>,
+}
+
+impl GenerateArgs {
+    /// Checks the combinations of settings which clap can not check, because some of the settings
+    /// may have come from `libgen.toml`.
+    fn validate(&self) -> anyhow::Result<()> {
+        let lenient = self.lenient.unwrap_or_default();
+        if lenient && self.extend.unwrap_or_default() {
+            anyhow::bail!("`lenient` can not be combined with `extend`");
+        }
+        if self.max_errors.is_some() && !lenient {
+            anyhow::bail!("`max-errors` requires `lenient`");
+        }
+        Ok(())
+    }
+
+    /// Takes every setting which was not given from the other arguments.
+    fn merge(&mut self, other: Self) {
+        if self.sections.is_empty() {
+            self.sections = other.sections;
+        }
+        self.order_outliers = self.order_outliers.or(other.order_outliers);
+        self.conflicts = self.conflicts.or(other.conflicts);
+        self.match_xrefs = self.match_xrefs.or(other.match_xrefs);
+        self.match_strings = self.match_strings.or(other.match_strings);
+        self.match_asm = self.match_asm.or(other.match_asm);
+        self.match_function_sizes = self.match_function_sizes.or(other.match_function_sizes);
+        self.interpolate = self.interpolate.or(other.interpolate);
+        self.stats = self.stats.or(other.stats);
+        self.extend = self.extend.or(other.extend);
+        self.lenient = self.lenient.or(other.lenient);
+        self.max_errors = self.max_errors.or(other.max_errors);
+        self.bin_format = self.bin_format.or(other.bin_format);
+        self.profile = self.profile.or(other.profile);
+    }
+}
+
+#[derive(Args)]
+struct SignaturesArgs {
+    #[arg(value_parser = input_directory_validator)]
+    input_directory: PathBuf,
+
+    /// The version to generate signatures for (e.g. `1.10.163`)
+    version: Version,
+
+    /// Where to write the signatures, defaults to `signatures-<version>.txt` in the input directory
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// The maximum length of a signature, in bytes
+    #[arg(long, default_value_t = 64)]
+    max_length: usize,
+
+    /// The game the address bins belong to, which decides their names and layout, defaults to the
+    /// profile in `libgen.toml`, or `fallout4`
+    #[arg(long, value_enum)]
+    profile: Option<Profile>,
+
+    /// The module whose inputs to use (e.g. `CreationKit`), defaults to the game itself. Outputs
+    /// written to the input directory are prefixed by the module
+    #[arg(long, default_value = "")]
+    module: String,
+}
+
+#[derive(Args)]
+struct LifecycleArgs {
+    #[arg(value_parser = input_directory_validator)]
+    input_directory: PathBuf,
+
+    /// The game the address bins belong to, which decides their names and layout, defaults to the
+    /// profile in `libgen.toml`, or `fallout4`
+    #[arg(long, value_enum)]
+    profile: Option<Profile>,
+
+    /// The module whose inputs to use (e.g. `CreationKit`), defaults to the game itself. Outputs
+    /// written to the input directory are prefixed by the module
+    #[arg(long, default_value = "")]
+    module: String,
+}
+
+#[derive(Args)]
+struct ReleaseNotesArgs {
+    #[arg(value_parser = input_directory_validator)]
+    input_directory: PathBuf,
+
+    /// A file listing well-known ids to check, one per line as `<id> [label]`
+    #[arg(long)]
+    watch_list: Option<PathBuf>,
+
+    /// Where to write the release notes, defaults to `release-notes-<version>.txt` in the input
+    /// directory
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// The game the address bins belong to, which decides their names and layout, defaults to the
+    /// profile in `libgen.toml`, or `fallout4`
+    #[arg(long, value_enum)]
+    profile: Option<Profile>,
+
+    /// The module whose inputs to use (e.g. `CreationKit`), defaults to the game itself. Outputs
+    /// written to the input directory are prefixed by the module
+    #[arg(long, default_value = "")]
+    module: String,
+}
+
+#[derive(Args)]
+struct DiffBinsArgs {
+    /// The old address bin
+    old: PathBuf,
+
+    /// The new address bin
+    new: PathBuf,
+
+    #[arg(long, value_enum, default_value_t)]
+    format: Format,
+
+    /// The game the address bin belongs to, which decides its layout
+    #[arg(long, value_enum, default_value_t)]
+    profile: Profile,
+
+    /// Where to write the diff, defaults to stdout
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct Bin2txtArgs {
+    /// The address bin to dump
+    input: PathBuf,
+
+    /// The version of the address bin, defaults to the version in its file name
+    #[arg(long)]
+    version: Option<Version>,
+
+    /// The game the address bin belongs to, which decides its layout
+    #[arg(long, value_enum, default_value_t)]
+    profile: Profile,
+
+    /// Where to write the text, defaults to the input with a `.txt` extension
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct Txt2binArgs {
+    /// The text to rebuild the address bin from
+    input: PathBuf,
+
+    /// The game the address bin belongs to, which decides its layout
+    #[arg(long, value_enum, default_value_t)]
+    profile: Profile,
+
+    /// Where to write the address bin, defaults to `version-<version>.bin` next to the input
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct ValidateBinArgs {
+    /// The address bins to check
+    #[arg(required = true)]
+    inputs: Vec<PathBuf>,
+
+    /// The game the address bins belong to, which decides their layout
+    #[arg(long, value_enum, default_value_t)]
+    profile: Profile,
+}
+
+#[derive(Args)]
+struct VerifyExeArgs {
+    #[arg(value_parser = input_directory_validator)]
+    input_directory: PathBuf,
+
+    /// The version to verify (e.g. `1.10.163`)
+    version: Version,
+
+    /// The executable to verify against, defaults to the executable in the idaexport directory of
+    /// the version
+    #[arg(long)]
+    exe: Option<PathBuf>,
+
+    /// Where to write the violations, defaults to `verify-<version>.txt` in the input directory
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// The game the address bins belong to, which decides their names and layout, defaults to the
+    /// profile in `libgen.toml`, or `fallout4`
+    #[arg(long, value_enum)]
+    profile: Option<Profile>,
+
+    /// The module whose inputs to use (e.g. `CreationKit`), defaults to the game itself. Outputs
+    /// written to the input directory are prefixed by the module
+    #[arg(long, default_value = "")]
+    module: String,
+}
+
+#[derive(Args)]
+struct ExeVersionArgs {
+    /// The executable to read the version from
+    exe: PathBuf,
+}
+
+#[derive(Args)]
+#[command(group = clap::ArgGroup::new("artifact").required(true).multiple(true))]
+struct IngestArgs {
+    /// The artifact directory to copy into
+    #[arg(value_parser = input_directory_validator)]
+    input_directory: PathBuf,
+
+    /// An idaexport directory, which is copied into a directory named after its version
+    #[arg(long, group = "artifact")]
+    idaexport: Option<PathBuf>,
+
+    /// The executable the idaexport directory was exported from, defaults to the executable within
+    /// the idaexport directory
+    #[arg(long, requires = "idaexport")]
+    exe: Option<PathBuf>,
+
+    /// The version of the idaexport directory, defaults to the version of the executable
+    #[arg(long, requires = "idaexport")]
+    version: Option<Version>,
+
+    /// A diff report, which is copied into a file named after the versions of its columns
+    #[arg(long, group = "artifact", requires_all = ["left", "right"])]
+    diff: Option<PathBuf>,
+
+    /// The version in the left column of the diff report
+    #[arg(long, requires = "diff")]
+    left: Option<Version>,
+
+    /// The version in the right column of the diff report
+    #[arg(long, requires = "diff")]
+    right: Option<Version>,
+
+    /// The module the artifacts belong to (e.g. `CreationKit`), defaults to the game itself. The
+    /// copies are prefixed by the module
+    #[arg(long, default_value = "")]
+    module: String,
+}
+
+#[derive(Args)]
+struct CheckInputsArgs {
     #[arg(value_parser = input_directory_validator)]
     input_directory: PathBuf,
+
+    /// The game the address bins belong to, which decides their names, defaults to the profile in
+    /// `libgen.toml`, or `fallout4`
+    #[arg(long, value_enum)]
+    profile: Option<Profile>,
 }
 
 fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
-    let (offset_lists, mut graph) =
-        OffsetLists::parse_all(&cli.input_directory).context("failed to parse all offsets")?;
+    match cli.command {
+        Some(Command::Signatures(args)) => signatures::write_signatures(
+            &args.input_directory,
+            args.version,
+            args.output.as_deref(),
+            args.max_length,
+            args.profile,
+            &args.module,
+        )
+        .context("failed to write signatures"),
+        Some(Command::Lifecycle(args)) => {
+            lifecycle::write_lifecycle(&args.input_directory, args.profile, &args.module)
+                .context("failed to write lifecycle")
+        }
+        Some(Command::ReleaseNotes(args)) => releasenotes::write_release_notes(
+            &args.input_directory,
+            args.watch_list.as_deref(),
+            args.output.as_deref(),
+            args.profile,
+            &args.module,
+        )
+        .context("failed to write release notes"),
+        Some(Command::DiffBins(args)) => bindiff::diff_bins(
+            &args.old,
+            &args.new,
+            args.profile,
+            args.format == Format::Json,
+            args.output.as_deref(),
+        )
+        .context("failed to diff address bins"),
+        Some(Command::Bin2txt(args)) => addrlib::bin_to_text(
+            &args.input,
+            args.version,
+            args.output.as_deref(),
+            args.profile,
+        )
+        .context("failed to dump address bin as text"),
+        Some(Command::Txt2bin(args)) => {
+            addrlib::text_to_bin(&args.input, args.output.as_deref(), args.profile)
+                .context("failed to rebuild address bin from text")
+        }
+        Some(Command::ValidateBin(args)) => addrlib::validate_bins(&args.inputs, args.profile),
+        Some(Command::VerifyExe(args)) => pe::verify_executable(
+            &args.input_directory,
+            args.version,
+            args.exe.as_deref(),
+            args.output.as_deref(),
+            args.profile,
+            &args.module,
+        )
+        .context("failed to verify executable"),
+        Some(Command::ExeVersion(args)) => pe::print_version(&args.exe),
+        Some(Command::Ingest(args)) => ingest(&args),
+        Some(Command::CheckInputs(args)) => {
+            inputs::check_inputs(&args.input_directory, args.profile)
+        }
+        None => generate(cli.generate),
+    }
+}
+
+fn ingest(args: &IngestArgs) -> anyhow::Result<()> {
+    if let Some(idaexport) = &args.idaexport {
+        ingest::ingest_idaexport(
+            &args.input_directory,
+            idaexport,
+            args.exe.as_deref(),
+            args.version,
+            &args.module,
+        )
+        .context("failed to ingest idaexport directory")?;
+    }
+    if let (Some(diff), Some(left), Some(right)) = (&args.diff, args.left, args.right) {
+        ingest::ingest_diff(&args.input_directory, diff, left, right, &args.module)
+            .context("failed to ingest diff report")?;
+    }
+    Ok(())
+}
+
+fn generate(mut cli: GenerateArgs) -> anyhow::Result<()> {
+    let input_directory = cli
+        .input_directory
+        .clone()
+        .context("an input directory is required")?;
+    let input_directory = input_directory.as_path();
+    let manifest = Manifest::load_with(input_directory, &mut cli)?;
+    cli.validate()?;
+    let mut errors = if cli.lenient.unwrap_or_default() {
+        ParseErrors::lenient(cli.max_errors.unwrap_or(1000))
+    } else {
+        ParseErrors::default()
+    };
+    let result = manifest.modules().iter().try_for_each(|module| {
+        if module.is_empty() {
+            return generate_module(&cli, &manifest.module(module), input_directory, &mut errors);
+        }
+        println!("generating {module}...");
+        generate_module(&cli, &manifest.module(module), input_directory, &mut errors)
+            .with_context(|| format!("failed to generate module '{module}'"))
+    });
+    errors.report();
+    result
+}
+
+/// Generates the address bins of a single module, whose ids are independent of every other
+/// module. Reports are written to the input directory, prefixed by the module.
+fn generate_module(
+    cli: &GenerateArgs,
+    manifest: &Manifest,
+    input_directory: &Path,
+    errors: &mut ParseErrors,
+) -> anyhow::Result<()> {
+    let report_path =
+        |name: &str| input_directory.join(manifest::module_prefix(manifest.name()) + name);
+    let (offset_lists, mut graph) = OffsetLists::parse_all(manifest, &cli.sections, errors)
+        .context("failed to parse all offsets")?;
 
-    {
-        let diff_lists =
-            DiffLists::parse_all(&cli.input_directory).context("failed to parse all diffs")?;
+    let mut diff_lists =
+        DiffLists::parse_all(manifest, errors).context("failed to parse all diffs")?;
+    let order_outliers = cli.order_outliers.unwrap_or_default();
+    if order_outliers != OrderOutliers::Ignore {
+        diff_lists
+            .check_order(
+                &offset_lists,
+                &report_path("order-outliers.txt"),
+                order_outliers == OrderOutliers::Drop,
+            )
+            .context("failed to check the order of diffs")?;
+    }
+    graph
+        .add_edges(&offset_lists, &diff_lists)
+        .context("failed to add edges from diff lists")?;
+
+    if cli.match_asm.unwrap_or_default() {
+        asm::match_functions(&mut graph, &offset_lists, errors)
+            .context("failed to match functions by instruction hashes")?;
+    }
+
+    if cli.match_strings.unwrap_or_default() {
+        strings::match_strings(&mut graph, &offset_lists, errors)
+            .context("failed to match string references")?;
+    }
+
+    if cli.match_xrefs.unwrap_or_default() {
+        xrefs::propagate(&mut graph, &offset_lists, errors)
+            .context("failed to propagate matches through xrefs")?;
+    }
+
+    if cli.match_function_sizes.unwrap_or_default() {
+        boundaries::match_function_sizes(&mut graph, &offset_lists);
+    }
+
+    if cli.interpolate.unwrap_or_default() {
+        interpolation::interpolate(&mut graph, &offset_lists);
+    }
+
+    if cli.conflicts.unwrap_or_default() != Conflicts::Ignore {
+        println!("checking for conflicts...");
+        let conflicts = graph.find_conflicts();
+        println!("\tfound {} conflicting components", conflicts.len());
         graph
-            .add_edges(&offset_lists, &diff_lists)
-            .context("failed to add edges from diff lists")?;
+            .write_conflicts(&conflicts, &report_path("conflicts.txt"))
+            .context("failed to write conflicts")?;
+        if cli.conflicts == Some(Conflicts::Split) {
+            let removed = graph.split_conflicts(conflicts);
+            println!("\tremoved {removed} edges to split conflicts");
+        }
     }
 
     let address_bins =
-        AddressBins::parse_all(&cli.input_directory).context("failed to parse all address bins")?;
+        AddressBins::parse_all(manifest, errors).context("failed to parse all address bins")?;
     graph
         .seed_ids(&offset_lists, &address_bins)
         .context("failed to seed ids from address bins")?;
@@ -54,8 +651,26 @@ fn main() -> anyhow::Result<()> {
     graph
         .assign_all_ids(largest_unused_id)
         .context("failed to assign ids to all offsets")?;
-    addrlib::write_bins(&cli.input_directory, &graph, &offset_lists, &address_bins)
-        .context("failed to write address bins")?;
+    segments::print_coverage(&graph, &offset_lists);
+    if cli.stats.unwrap_or_default() {
+        stats::write_stats(
+            &report_path("stats.txt"),
+            &graph,
+            &offset_lists,
+            &diff_lists,
+            &address_bins,
+            largest_unused_id,
+        )
+        .context("failed to write stats")?;
+    }
+    addrlib::write_bins(
+        manifest,
+        &graph,
+        &offset_lists,
+        &address_bins,
+        cli.extend.unwrap_or_default(),
+    )
+    .context("failed to write address bins")?;
 
     Ok(())
 }
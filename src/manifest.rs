@@ -0,0 +1,498 @@
+use crate::{
+    addrlib::{
+        self,
+        BinFormat,
+    },
+    common::Version,
+    profile::Profile,
+    GenerateArgs,
+};
+use anyhow::Context as _;
+use regex_lite::Regex;
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+use walkdir::WalkDir;
+
+/// The name of the optional file within the root directory which lists the inputs explicitly.
+pub const FILE_NAME: &str = "libgen.toml";
+
+/// Matches the optional module a file or directory name starts with, e.g. `CreationKit-`. Any
+/// name in front of the version is taken as a module, so patterns using this must be anchored.
+pub const MODULE_PATTERN: &str = r"(?:([A-Za-z]\w*)-)?";
+
+/// The prefix of the names belonging to the given module. The names belonging to the game itself,
+/// whose module is empty, have no prefix.
+pub fn module_prefix(module: &str) -> String {
+    if module.is_empty() {
+        String::new()
+    } else {
+        format!("{module}-")
+    }
+}
+
+/// The name of a report about the given version of a module, e.g.
+/// `CreationKit-verify-1-10-163-0.txt`.
+pub fn report_name(module: &str, name: &str, version: Version) -> String {
+    format!(
+        "{}{name}-{}-{}-{}-{}.txt",
+        module_prefix(module),
+        version[0],
+        version[1],
+        version[2],
+        version[3]
+    )
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    #[serde(default, rename = "version")]
+    versions: Vec<VersionConfig>,
+
+    #[serde(default, rename = "diff")]
+    diffs: Vec<DiffConfig>,
+
+    /// Settings for generation, named after their command line flags.
+    #[serde(default)]
+    generate: GenerateArgs,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct VersionConfig {
+    #[serde(default)]
+    module: String,
+    version: Version,
+    idaexport: PathBuf,
+    bin: Option<PathBuf>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DiffConfig {
+    #[serde(default)]
+    module: String,
+    left: Version,
+    right: Version,
+    path: PathBuf,
+}
+
+#[derive(Clone)]
+pub struct DiffFile {
+    pub module: String,
+    pub left: Version,
+    pub right: Version,
+    pub path: PathBuf,
+}
+
+/// The inputs within the root directory, either listed by `libgen.toml`, or located by the names
+/// of the files and directories. Every input belongs to a module, which is either the game itself
+/// or another executable such as `CreationKit`, and each module has a separate id space.
+#[derive(Clone)]
+pub struct Manifest {
+    root_dir: PathBuf,
+    /// The module which new bins are named after, see [`Manifest::module`].
+    module: String,
+    idaexports: BTreeMap<(String, Version), PathBuf>,
+    diffs: Vec<DiffFile>,
+    /// Where the bin of each version is read from, and written to if it does not exist yet.
+    bins: BTreeMap<(String, Version), PathBuf>,
+    profile: Profile,
+    format: BinFormat,
+}
+
+impl Manifest {
+    /// Loads the manifest of the root directory for the given profile, which defaults to the
+    /// profile in `libgen.toml`.
+    pub fn load(root_dir: &Path, profile: Option<Profile>) -> anyhow::Result<Self> {
+        Self::load_with(
+            root_dir,
+            &mut GenerateArgs {
+                profile,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Loads the manifest of the root directory. Any settings from `libgen.toml` which were not
+    /// given on the command line are merged into the given arguments.
+    pub fn load_with(root_dir: &Path, args: &mut GenerateArgs) -> anyhow::Result<Self> {
+        let path = root_dir.join(FILE_NAME);
+        if path.exists() {
+            println!("reading {FILE_NAME}...");
+            let src = fs::read_to_string(&path)
+                .with_context(|| format!("failed to read file: {}", path.display()))?;
+            Self::from_config(root_dir, &src, args)
+                .with_context(|| format!("failed to parse config: {}", path.display()))
+        } else {
+            Self::discover(
+                root_dir,
+                args.profile.unwrap_or_default(),
+                args.bin_format.unwrap_or_default(),
+            )
+        }
+    }
+
+    fn from_config(root_dir: &Path, src: &str, args: &mut GenerateArgs) -> anyhow::Result<Self> {
+        let config: Config = toml::from_str(src)?;
+        args.merge(config.generate);
+        let profile = args.profile.unwrap_or_default();
+        let format = args.bin_format.unwrap_or_default();
+
+        let mut idaexports = BTreeMap::new();
+        let mut bins = BTreeMap::new();
+        for entry in config.versions {
+            let key = (entry.module, entry.version);
+            let bin = entry.bin.unwrap_or_else(|| {
+                format!(
+                    "{}{}",
+                    module_prefix(&key.0),
+                    addrlib::file_name(key.1, profile, format)
+                )
+                .into()
+            });
+            bins.insert(key.clone(), root_dir.join(bin));
+            if idaexports
+                .insert(key.clone(), root_dir.join(entry.idaexport))
+                .is_some()
+            {
+                anyhow::bail!(
+                    "version '{}' is listed more than once for module '{}'",
+                    key.1,
+                    key.0
+                );
+            }
+        }
+
+        let mut diffs = Vec::new();
+        for entry in config.diffs {
+            if entry.left == entry.right {
+                anyhow::bail!(
+                    "found a diff that maps from one version to itself: {:?}",
+                    entry.path
+                );
+            }
+            for version in [entry.left, entry.right] {
+                if !idaexports.contains_key(&(entry.module.clone(), version)) {
+                    anyhow::bail!(
+                        "found diff for version '{version}', but the version is not listed: {:?}",
+                        entry.path
+                    );
+                }
+            }
+            diffs.push(DiffFile {
+                module: entry.module,
+                left: entry.left,
+                right: entry.right,
+                path: root_dir.join(entry.path),
+            });
+        }
+
+        Ok(Self {
+            root_dir: root_dir.to_owned(),
+            module: String::new(),
+            idaexports,
+            diffs,
+            bins,
+            profile,
+            format,
+        })
+    }
+
+    /// Locates the inputs by their names: idaexport directories named after their version (e.g.
+    /// `1.10.163`), diffs named after the versions of their columns (e.g.
+    /// `1.10.130_1.10.138.txt`), and bins named after their version (e.g.
+    /// `version-1-10-163-0.bin`, depending on the profile). Any of these names may start with a
+    /// module (e.g. `CreationKit-1.10.163`).
+    fn discover(root_dir: &Path, profile: Profile, format: BinFormat) -> anyhow::Result<Self> {
+        let dir_pattern = Regex::new(&format!(r"^{MODULE_PATTERN}(\d+)\.(\d+)\.(\d+)$"))
+            .context("failed to build directory pattern")?;
+        let diff_pattern = Regex::new(&format!(
+            r"^{MODULE_PATTERN}(\d+)\.(\d+)\.(\d+)_(\d+)\.(\d+)\.(\d+)\.txt$"
+        ))
+        .context("failed to build diff pattern")?;
+        let bin_pattern = addrlib::file_name_pattern(profile, format)?;
+
+        let mut idaexports = BTreeMap::new();
+        let mut diffs = Vec::new();
+        let mut bins = BTreeMap::new();
+        for dir_entry in WalkDir::new(root_dir) {
+            let dir_entry = dir_entry.with_context(|| {
+                format!(
+                    "error while locating inputs in directory: {}",
+                    root_dir.display()
+                )
+            })?;
+            let path = dir_entry.path();
+            let metadata = dir_entry.metadata().with_context(|| {
+                format!(
+                    "failed to get metadata for directory entry: {}",
+                    path.display()
+                )
+            })?;
+            let Some(file_name) = path.file_name().and_then(|x| x.to_str()) else {
+                continue;
+            };
+
+            if metadata.is_dir() {
+                if let Some(captures) = dir_pattern.captures(file_name) {
+                    let module = captures.get(1).map_or("", |x| x.as_str());
+                    let version = (&captures[2], &captures[3], &captures[4])
+                        .try_into()
+                        .with_context(|| {
+                            format!(
+                                "failed to construct version from directory name: {}",
+                                path.display()
+                            )
+                        })?;
+                    idaexports.insert((module.to_owned(), version), path.to_owned());
+                }
+            } else if let Some(key) = addrlib::parse_file_name(&bin_pattern, path)? {
+                bins.insert(key, path.to_owned());
+            } else if let Some(captures) = diff_pattern.captures(file_name) {
+                let module = captures.get(1).map_or("", |x| x.as_str());
+                let parse_version = |i1, i2, i3| {
+                    Version::try_from((&captures[i1], &captures[i2], &captures[i3])).with_context(
+                        || format!("failed to parse version from file name: {}", path.display()),
+                    )
+                };
+                let left = parse_version(2, 3, 4)?;
+                let right = parse_version(5, 6, 7)?;
+                if left == right {
+                    anyhow::bail!(
+                        "found a diff file that maps from one version to itself: {path:?}"
+                    );
+                }
+                diffs.push(DiffFile {
+                    module: module.to_owned(),
+                    left,
+                    right,
+                    path: path.to_owned(),
+                });
+            }
+        }
+
+        Ok(Self {
+            root_dir: root_dir.to_owned(),
+            module: String::new(),
+            idaexports,
+            diffs,
+            bins,
+            profile,
+            format,
+        })
+    }
+
+    /// The modules which have at least one idaexport directory.
+    pub fn modules(&self) -> Vec<String> {
+        let mut result = self
+            .idaexports
+            .keys()
+            .map(|(module, _)| module.clone())
+            .collect::<Vec<_>>();
+        result.dedup();
+        result
+    }
+
+    /// Narrows the manifest down to the inputs of the given module.
+    pub fn module(&self, module: &str) -> Self {
+        let mut result = self.clone();
+        module.clone_into(&mut result.module);
+        result.idaexports.retain(|(x, _), _| x == module);
+        result.diffs.retain(|x| x.module == module);
+        result.bins.retain(|(x, _), _| x == module);
+        result
+    }
+
+    /// The module the manifest has been narrowed down to, which is empty for the game itself.
+    pub fn name(&self) -> &str {
+        &self.module
+    }
+
+    /// Iterates over the idaexport directories of every module. The versions are only unique when
+    /// the manifest has been narrowed down to a single module.
+    pub fn idaexports(&self) -> impl Iterator<Item = (Version, &Path)> {
+        self.idaexports.iter().map(|((_, k), v)| (*k, v.as_path()))
+    }
+
+    pub fn diffs(&self) -> impl Iterator<Item = &DiffFile> {
+        self.diffs.iter()
+    }
+
+    /// Iterates over the bins which already exist.
+    pub fn existing_bins(&self) -> impl Iterator<Item = (Version, &Path)> {
+        self.bins
+            .iter()
+            .filter(|(_, v)| v.exists())
+            .map(|((_, k), v)| (*k, v.as_path()))
+    }
+
+    pub fn bin_path(&self, version: Version) -> PathBuf {
+        self.bins
+            .get(&(self.module.clone(), version))
+            .cloned()
+            .unwrap_or_else(|| {
+                self.root_dir.join(format!(
+                    "{}{}",
+                    module_prefix(&self.module),
+                    addrlib::file_name(version, self.profile, self.format)
+                ))
+            })
+    }
+
+    pub fn profile(&self) -> Profile {
+        self.profile
+    }
+
+    pub fn format(&self) -> BinFormat {
+        self.format
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Manifest;
+    use crate::{
+        addrlib::BinFormat,
+        common,
+        profile::Profile,
+        GenerateArgs,
+    };
+    use std::{
+        fs,
+        path::Path,
+    };
+
+    #[test]
+    fn test_config() -> anyhow::Result<()> {
+        let src = r#"
+[[version]]
+version = "1.10.130"
+idaexport = "exports/130"
+bin = "bins/version-1-10-130-0.bin"
+
+[[version]]
+version = "1.10.138"
+idaexport = "exports/138"
+
+[[version]]
+module = "CreationKit"
+version = "1.10.138"
+idaexport = "exports/ck138"
+
+[[diff]]
+left = "1.10.130"
+right = "1.10.138"
+path = "diffs/130_138.txt"
+
+[generate]
+match-xrefs = true
+interpolate = true
+order-outliers = "drop"
+bin-format = "text"
+"#;
+        let root = Path::new("root");
+        let mut args = GenerateArgs {
+            match_asm: Some(true),
+            match_xrefs: Some(false),
+            ..Default::default()
+        };
+        let all = Manifest::from_config(root, src, &mut args)?;
+        assert_eq!(all.modules(), ["", "CreationKit"]);
+        let manifest = all.module("");
+        assert!(args.match_asm == Some(true) && args.match_xrefs == Some(false));
+        assert!(args.interpolate == Some(true));
+        assert!(args.order_outliers == Some(crate::OrderOutliers::Drop));
+        assert!(manifest.format() == BinFormat::Text);
+
+        let idaexports = manifest
+            .idaexports()
+            .map(|(_, x)| x.to_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            idaexports,
+            [root.join("exports/130"), root.join("exports/138")]
+        );
+        let diff = manifest.diffs().next().unwrap();
+        assert_eq!(diff.path, root.join("diffs/130_138.txt"));
+        assert_eq!(
+            manifest.bin_path("1.10.130".parse()?),
+            root.join("bins/version-1-10-130-0.bin")
+        );
+        assert_eq!(
+            manifest.bin_path("1.10.138".parse()?),
+            root.join("version-1-10-138-0.txt")
+        );
+
+        let creation_kit = all.module("CreationKit");
+        assert_eq!(creation_kit.idaexports().count(), 1);
+        assert_eq!(creation_kit.diffs().count(), 0);
+        assert_eq!(
+            creation_kit.bin_path("1.10.138".parse()?),
+            root.join("CreationKit-version-1-10-138-0.txt")
+        );
+
+        assert!(args.validate().is_ok());
+        let mut args = GenerateArgs::default();
+        Manifest::from_config(root, "[generate]\nmax-errors = 5\n", &mut args)?;
+        assert!(args.validate().is_err());
+
+        let unknown = "[[diff]]\nleft = \"1.10.130\"\nright = \"1.10.138\"\npath = \"x.txt\"\n";
+        assert!(Manifest::from_config(root, unknown, &mut GenerateArgs::default()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_discover_modules() -> anyhow::Result<()> {
+        let root = common::test_dir("discover-modules");
+        // names with anything around the version, other than a module in front, are ignored
+        for dir in [
+            "1.10.130",
+            "CreationKit-1.10.130",
+            "CreationKit-1.10.138",
+            "1.10.138.bak",
+            "v1.10.138",
+        ] {
+            fs::create_dir(root.join(dir))?;
+        }
+        for file in [
+            "version-1-10-130-0.bin",
+            "version-1-10-138-0.bin.bak",
+            "CreationKit-version-1-10-130-0.bin",
+            "CreationKit-1.10.130_1.10.138.txt",
+            "CreationKit-1.10.130_1.10.138.txt.orig",
+        ] {
+            fs::write(root.join(file), "")?;
+        }
+
+        let all = Manifest::discover(&root, Profile::Fallout4, BinFormat::Binary)?;
+        assert_eq!(all.modules(), ["", "CreationKit"]);
+        let game = all.module("");
+        assert_eq!(game.idaexports().count(), 1);
+        let bins = game.existing_bins().map(|(_, x)| x).collect::<Vec<_>>();
+        assert_eq!(bins, [root.join("version-1-10-130-0.bin")]);
+        assert_eq!(game.diffs().count(), 0);
+
+        let creation_kit = all.module("CreationKit");
+        assert_eq!(creation_kit.idaexports().count(), 2);
+        let bins = creation_kit
+            .existing_bins()
+            .map(|(_, x)| x)
+            .collect::<Vec<_>>();
+        assert_eq!(bins, [root.join("CreationKit-version-1-10-130-0.bin")]);
+        assert_eq!(creation_kit.diffs().count(), 1);
+        assert_eq!(
+            creation_kit.bin_path("1.10.138".parse()?),
+            root.join("CreationKit-version-1-10-138-0.bin")
+        );
+        fs::remove_dir_all(root)?;
+        Ok(())
+    }
+}
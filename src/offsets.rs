@@ -3,14 +3,31 @@ use crate::{
         Offset,
         Version,
     },
+    errors::{
+        FileErrors,
+        ParseErrors,
+    },
     graph::{
         Graph,
         Ix,
     },
+    manifest::Manifest,
+    pe::{
+        self,
+        Executable,
+        Section,
+    },
+    segments::{
+        Segment,
+        SegmentList,
+    },
 };
 use anyhow::Context as _;
 use petgraph::graph::NodeIndex;
-use regex_lite::Regex;
+use regex_lite::{
+    Captures,
+    Regex,
+};
 use std::{
     collections::BTreeMap,
     fs::File,
@@ -18,64 +35,240 @@ use std::{
         BufRead,
         BufReader,
     },
-    path::Path,
+    ops::RangeBounds,
+    path::{
+        Path,
+        PathBuf,
+    },
 };
-use walkdir::WalkDir;
+
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum Kind {
+    Function,
+    Global,
+    Name,
+}
 
 pub struct Mapping {
     pub ix: NodeIndex<Ix>,
+    /// The kind of symbol found at the offset, preferring functions over globals over names when
+    /// an offset appears in more than one export.
+    pub kind: Kind,
+}
+
+pub struct Function {
+    pub start: Offset,
+    pub end: Offset,
+}
+
+impl Function {
+    pub fn size(&self) -> u32 {
+        self.end.0 - self.start.0
+    }
 }
 
 pub struct OffsetList {
     offsets: BTreeMap<Offset, Mapping>,
+    segments: SegmentList,
+    functions: Vec<Function>,
+    /// The names from `idaexport_name.txt`, demangled where possible.
+    names: BTreeMap<Offset, String>,
+    idaexport: PathBuf,
+    base_address: u64,
+    /// Whether the offsets were built from the executable, in which case there are no idaexport
+    /// files to read xrefs, strings or instructions from.
+    from_executable: bool,
 }
 
 impl OffsetList {
-    const FUNCTION_PATTERN: &'static str = r"func\t([\dA-Fa-f]+)\t[\dA-Fa-f]+";
+    const FUNCTION_PATTERN: &'static str = r"func\t([\dA-Fa-f]+)\t([\dA-Fa-f]+)";
     const GLOBAL_PATTERN: &'static str = r"global\t([\dA-Fa-f]+)";
-    const NAME_PATTERN: &'static str = r"name\t([\dA-Fa-f]+)";
-
-    fn parse(idaexport: &Path, graph: &mut Graph) -> anyhow::Result<Self> {
-        let buffer_reader = |file_name| -> anyhow::Result<_> {
-            let path = idaexport.join(file_name);
-            let file =
-                File::open(&path).with_context(|| format!("failed to open file: {path:?}"))?;
-            Ok(BufReader::new(file))
-        };
-
-        let offsets = {
-            let base_address = {
-                let mut file = buffer_reader("idaexport_base.txt")?;
-                Self::parse_base_address(&mut file).context("failed to parse idaexport_base.txt")
-            }?;
-            let do_parse = |file_name, pattern| {
-                let mut file = buffer_reader(file_name)?;
-                Self::parse_generic_offsets(&mut file, base_address, pattern)
-                    .with_context(|| format!("failed to parse {file_name}"))
-            };
-            let function_offsets = do_parse("idaexport_func.txt", Self::FUNCTION_PATTERN)?;
-            let global_offsets = do_parse("idaexport_global.txt", Self::GLOBAL_PATTERN)?;
-            let name_offsets = do_parse("idaexport_name.txt", Self::NAME_PATTERN)?;
+    const NAME_PATTERN: &'static str = r"name\t([\dA-Fa-f]+)(?:\t([^\t\r\n]*)(?:\t([^\r\n]+))?)?";
 
-            function_offsets
-                .into_iter()
-                .chain(global_offsets)
-                .chain(name_offsets)
-                .map(|x| {
-                    (
-                        x,
-                        Mapping {
-                            ix: graph.add_node(),
-                        },
-                    )
+    fn parse(
+        idaexport: &Path,
+        version: Version,
+        graph: &mut Graph,
+        sections: &[String],
+        errors: &mut ParseErrors,
+    ) -> anyhow::Result<Self> {
+        let buffer_reader = |file_name| Self::open_file(idaexport, file_name);
+
+        let base_address = {
+            let mut file = buffer_reader("idaexport_base.txt")?;
+            let path = idaexport.join("idaexport_base.txt");
+            Self::parse_base_address(&mut file, &mut errors.file(&path))
+                .context("failed to parse idaexport_base.txt")
+        }?;
+        let segments = {
+            let mut file = buffer_reader("idaexport_segment.txt")?;
+            let path = idaexport.join("idaexport_segment.txt");
+            SegmentList::parse(&mut file, base_address, &mut errors.file(&path))
+                .context("failed to parse idaexport_segment.txt")
+        }?;
+
+        let functions = {
+            let mut file = buffer_reader("idaexport_func.txt")?;
+            let path = idaexport.join("idaexport_func.txt");
+            Self::parse_functions(&mut file, base_address, &mut errors.file(&path))
+                .context("failed to parse idaexport_func.txt")
+        }?;
+        let names = {
+            let mut file = buffer_reader("idaexport_name.txt")?;
+            let path = idaexport.join("idaexport_name.txt");
+            Self::parse_names(&mut file, base_address, &mut errors.file(&path))
+                .context("failed to parse idaexport_name.txt")
+        }?;
+        let global_offsets = {
+            let mut file = buffer_reader("idaexport_global.txt")?;
+            let path = idaexport.join("idaexport_global.txt");
+            Self::parse_generic_offsets(
+                &mut file,
+                base_address,
+                Self::GLOBAL_PATTERN,
+                &mut errors.file(&path),
+            )
+            .context("failed to parse idaexport_global.txt")
+        }?;
+
+        Ok(Self::new(
+            idaexport,
+            version,
+            graph,
+            sections,
+            base_address,
+            segments,
+            functions,
+            global_offsets,
+            names,
+        ))
+    }
+
+    /// Builds an offset list directly from the executable within the given directory, for when
+    /// there are no idaexport files. Functions are taken from the exception directory, and globals
+    /// from the targets of the base relocations which lie within data sections.
+    fn from_executable(
+        directory: &Path,
+        executable: &Executable,
+        version: Version,
+        graph: &mut Graph,
+        sections: &[String],
+    ) -> anyhow::Result<Self> {
+        let segments = executable
+            .sections()
+            .iter()
+            .map(|x| {
+                let end = x
+                    .virtual_address
+                    .checked_add(x.virtual_size)
+                    .with_context(|| format!("section extends past the image: {}", x.name))?;
+                Ok(Segment {
+                    start: Offset(x.virtual_address),
+                    end: Offset(end),
+                    name: x.name.clone(),
                 })
-                .collect()
-        };
+            })
+            .collect::<anyhow::Result<_>>()?;
+        let functions = executable
+            .runtime_functions()
+            .context("failed to read exception directory")?
+            .into_iter()
+            .filter(|x| !x.chained)
+            .map(|x| Function {
+                start: x.begin,
+                end: x.end,
+            })
+            .collect();
+        let global_offsets = executable
+            .relocation_targets()
+            .context("failed to read base relocations")?
+            .into_iter()
+            .filter(|x| {
+                executable
+                    .section_containing(*x)
+                    .is_some_and(Section::is_data)
+            })
+            .collect();
 
-        Ok(Self { offsets })
+        let mut result = Self::new(
+            directory,
+            version,
+            graph,
+            sections,
+            executable.image_base(),
+            segments,
+            functions,
+            global_offsets,
+            Vec::new(),
+        );
+        result.from_executable = true;
+        Ok(result)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        idaexport: &Path,
+        version: Version,
+        graph: &mut Graph,
+        sections: &[String],
+        base_address: u64,
+        segments: SegmentList,
+        mut functions: Vec<Function>,
+        global_offsets: Vec<Offset>,
+        names: Vec<(Offset, Option<String>)>,
+    ) -> Self {
+        let mut offsets = BTreeMap::new();
+        let all_offsets = functions
+            .iter()
+            .map(|x| (x.start, Kind::Function))
+            .chain(global_offsets.into_iter().map(|x| (x, Kind::Global)))
+            .chain(names.iter().map(|(x, _)| (*x, Kind::Name)))
+            .filter(|(x, _)| {
+                sections.is_empty()
+                    || segments
+                        .find(*x)
+                        .is_some_and(|segment| sections.contains(&segment.name))
+            });
+        // every export still gets a node of its own, and the last one wins, since the nodes of
+        // duplicate offsets consume fresh ids as well
+        for (offset, kind) in all_offsets {
+            let ix = graph.add_node(version, offset);
+            offsets
+                .entry(offset)
+                .and_modify(|x: &mut Mapping| {
+                    x.ix = ix;
+                    x.kind = x.kind.min(kind);
+                })
+                .or_insert(Mapping { ix, kind });
+        }
+
+        functions.sort_by_key(|x| x.start);
+        functions.dedup_by_key(|x| x.start);
+        Self {
+            offsets,
+            segments,
+            functions,
+            names: names
+                .into_iter()
+                .filter_map(|(offset, name)| Some((offset, name?)))
+                .collect(),
+            idaexport: idaexport.to_owned(),
+            base_address,
+            from_executable: false,
+        }
     }
 
-    fn parse_base_address<R: BufRead>(idaexport_base: &mut R) -> anyhow::Result<u64> {
+    fn open_file(idaexport: &Path, file_name: &str) -> anyhow::Result<BufReader<File>> {
+        let path = idaexport.join(file_name);
+        let file = File::open(&path)
+            .with_context(|| format!("failed to open file: {}", path.display()))?;
+        Ok(BufReader::new(file))
+    }
+
+    fn parse_base_address<R: BufRead>(
+        idaexport_base: &mut R,
+        errors: &mut FileErrors,
+    ) -> anyhow::Result<u64> {
         let mut buffer = String::new();
         macro_rules! read_line {
             () => {{
@@ -97,28 +290,80 @@ impl OffsetList {
         }
 
         read_line!().context("failed to read base address")?;
-        let captures = address_pattern
+        let base_address = address_pattern
             .captures(&buffer)
-            .context("failed to match base address pattern")?;
-        u64::from_str_radix(&captures[1], 16)
-            .with_context(|| format!("failed to parse base address: {}", &captures[1]))
+            .context("failed to match base address pattern")
+            .and_then(|captures| {
+                u64::from_str_radix(&captures[1], 16)
+                    .with_context(|| format!("failed to parse base address: {}", &captures[1]))
+            });
+        errors
+            .check(2, base_address)?
+            .context("found no base address to parse the other files with")
     }
 
     fn parse_generic_offsets<R: BufRead>(
         idaexport: &mut R,
         base_address: u64,
         pattern: &str,
+        errors: &mut FileErrors,
     ) -> anyhow::Result<Vec<Offset>> {
+        Self::parse_generic(idaexport, pattern, errors, |captures| {
+            Ok(Offset(Self::parse_offset(base_address, &captures[1])?))
+        })
+    }
+
+    fn parse_functions<R: BufRead>(
+        idaexport: &mut R,
+        base_address: u64,
+        errors: &mut FileErrors,
+    ) -> anyhow::Result<Vec<Function>> {
+        Self::parse_generic(idaexport, Self::FUNCTION_PATTERN, errors, |captures| {
+            let start = Offset(Self::parse_offset(base_address, &captures[1])?);
+            let end = Offset(Self::parse_offset(base_address, &captures[2])?);
+            if end < start {
+                anyhow::bail!("function ends before it starts: {start} > {end}");
+            }
+            Ok(Function { start, end })
+        })
+    }
+
+    fn parse_names<R: BufRead>(
+        idaexport: &mut R,
+        base_address: u64,
+        errors: &mut FileErrors,
+    ) -> anyhow::Result<Vec<(Offset, Option<String>)>> {
+        Self::parse_generic(idaexport, Self::NAME_PATTERN, errors, |captures| {
+            let offset = Offset(Self::parse_offset(base_address, &captures[1])?);
+            let name = captures
+                .get(3)
+                .or_else(|| captures.get(2))
+                .map(|x| x.as_str())
+                .filter(|x| !x.is_empty());
+            Ok((offset, name.map(ToOwned::to_owned)))
+        })
+    }
+
+    /// Parses every line after the version header with the given pattern, passing the lines which
+    /// fail to match or parse to the given errors.
+    pub fn parse_generic<R: BufRead, T, F: FnMut(&Captures) -> anyhow::Result<T>>(
+        idaexport: &mut R,
+        pattern: &str,
+        errors: &mut FileErrors,
+        mut parse_captures: F,
+    ) -> anyhow::Result<Vec<T>> {
         let mut buffer = String::new();
+        let mut line = 0;
         macro_rules! read_line {
             () => {{
                 buffer.clear();
+                line += 1;
                 idaexport.read_line(&mut buffer)
             }};
         }
         let version_pattern =
             Regex::new(r"version\t(\d+)").context("failed to build version pattern")?;
-        let name_pattern = Regex::new(pattern).context("failed to build offset pattern")?;
+        let line_pattern = Regex::new(pattern).context("failed to build line pattern")?;
 
         read_line!().context("failed to read version")?;
         let captures = version_pattern
@@ -126,27 +371,27 @@ impl OffsetList {
             .context("failed to match version pattern")?;
         if &captures[1] != "1" {
             anyhow::bail!("unsupported version: {}", &captures[1]);
-        };
+        }
 
-        let mut offsets = Vec::new();
+        let mut result = Vec::new();
         loop {
             break match read_line!() {
-                Ok(0) => Ok(offsets),
-                Ok(_) if buffer.trim().is_empty() => Ok(offsets),
+                Ok(0) => Ok(result),
+                Ok(_) if buffer.trim().is_empty() => Ok(result),
                 Ok(_) => {
-                    let captures = name_pattern
+                    let parsed = line_pattern
                         .captures(&buffer)
-                        .context("failed to match offset pattern")?;
-                    let offset = Self::parse_offset(base_address, &captures[1])?;
-                    offsets.push(Offset(offset));
+                        .context("failed to match line pattern")
+                        .and_then(|x| parse_captures(&x));
+                    result.extend(errors.check(line, parsed)?);
                     continue;
                 }
-                Err(err) => Err(err).context("failed to read offset"),
+                Err(err) => Err(err).context("failed to read line"),
             };
         }
     }
 
-    fn parse_offset(base_address: u64, string: &str) -> anyhow::Result<u32> {
+    pub fn parse_offset(base_address: u64, string: &str) -> anyhow::Result<u32> {
         let address = u64::from_str_radix(string, 16)
             .with_context(|| format!("failed to parse address: {string}"))?;
         let offset: u32 = address
@@ -164,6 +409,66 @@ impl OffsetList {
     pub fn iter(&self) -> impl Iterator<Item = (&Offset, &Mapping)> {
         self.offsets.iter()
     }
+
+    pub fn range<R: RangeBounds<Offset>>(
+        &self,
+        range: R,
+    ) -> impl Iterator<Item = (&Offset, &Mapping)> {
+        self.offsets.range(range)
+    }
+
+    pub fn segments(&self) -> &SegmentList {
+        &self.segments
+    }
+
+    pub fn base_address(&self) -> u64 {
+        self.base_address
+    }
+
+    /// Whether the idaexport files exist, rather than the offsets having been built from the
+    /// executable.
+    pub fn has_idaexport(&self) -> bool {
+        !self.from_executable
+    }
+
+    /// The directory this offset list was exported to.
+    pub fn directory(&self) -> &Path {
+        &self.idaexport
+    }
+
+    /// Opens one of the other files that were exported alongside this offset list.
+    pub fn open(&self, file_name: &str) -> anyhow::Result<BufReader<File>> {
+        Self::open_file(&self.idaexport, file_name)
+    }
+
+    /// All functions, ordered by their start.
+    pub fn functions(&self) -> &[Function] {
+        &self.functions
+    }
+
+    pub fn function(&self, start: Offset) -> Option<&Function> {
+        self.functions
+            .binary_search_by_key(&start, |x| x.start)
+            .ok()
+            .map(|x| &self.functions[x])
+    }
+
+    pub fn name(&self, offset: Offset) -> Option<&str> {
+        self.names.get(&offset).map(String::as_str)
+    }
+
+    pub fn is_function(&self, offset: Offset) -> bool {
+        self.function(offset).is_some()
+    }
+
+    /// Finds the start of the function which contains the given offset.
+    pub fn function_containing(&self, offset: Offset) -> Option<Offset> {
+        let pos = self.functions.partition_point(|x| x.start <= offset);
+        pos.checked_sub(1)
+            .map(|x| &self.functions[x])
+            .filter(|x| offset < x.end)
+            .map(|x| x.start)
+    }
 }
 
 pub struct OffsetLists {
@@ -171,37 +476,58 @@ pub struct OffsetLists {
 }
 
 impl OffsetLists {
-    pub fn parse_all(root_dir: &Path) -> anyhow::Result<(Self, Graph)> {
+    pub fn parse_all(
+        manifest: &Manifest,
+        sections: &[String],
+        errors: &mut ParseErrors,
+    ) -> anyhow::Result<(Self, Graph)> {
+        Self::parse_matching(manifest, sections, errors, |_| true)
+    }
+
+    /// Parses only the offset lists for the versions accepted by the given predicate.
+    pub fn parse_matching<P: Fn(Version) -> bool>(
+        manifest: &Manifest,
+        sections: &[String],
+        errors: &mut ParseErrors,
+        predicate: P,
+    ) -> anyhow::Result<(Self, Graph)> {
         println!("parsing offsets...");
 
         let mut db = BTreeMap::default();
         let mut graph = Graph::default();
-        let dir_pattern =
-            Regex::new(r"(\d+)\.(\d+)\.(\d+)").context("failed to build directory pattern")?;
-
-        for dir_entry in WalkDir::new(root_dir) {
-            let dir_entry = dir_entry.with_context(|| {
-                format!("error while locating idaexport directories in directory: {root_dir:?}")
-            })?;
-            let path = dir_entry.path();
-            let metadata = dir_entry
-                .metadata()
-                .with_context(|| format!("failed to get metadata for directory entry: {path:?}"))?;
-            if metadata.is_dir() {
-                if let Some(file_name) = path.file_name().and_then(|x| x.to_str()) {
-                    if let Some(captures) = dir_pattern.captures(file_name) {
-                        let version: Version = (&captures[1], &captures[2], &captures[3])
-                            .try_into()
-                            .with_context(|| {
-                                format!("failed to construct version from directory name: {path:?}")
-                            })?;
-                        let offsets = OffsetList::parse(path, &mut graph).with_context(|| {
-                            format!("failed to parse offset list from directory: {path:?}")
-                        })?;
-                        db.insert(version, offsets);
-                    }
+        for (version, path) in manifest.idaexports() {
+            if !predicate(version) {
+                continue;
+            }
+            // a mislabeled executable is caught even when the offsets come from idaexport files
+            let executable = match Executable::find(path)? {
+                Some(executable) => {
+                    let loaded = Executable::load(&executable)?;
+                    pe::check_version(&loaded, &executable, version)?;
+                    Some(loaded)
+                }
+                None => None,
+            };
+            let offsets = match executable {
+                Some(executable) if !path.join("idaexport_base.txt").exists() => {
+                    OffsetList::from_executable(path, &executable, version, &mut graph, sections)
                 }
+                _ => OffsetList::parse(path, version, &mut graph, sections, errors),
             }
+            .with_context(|| {
+                format!(
+                    "failed to parse offset list from directory: {}",
+                    path.display()
+                )
+            })?;
+            let expected = manifest.profile().base_address();
+            if offsets.base_address() != expected {
+                anyhow::bail!(
+                    "version '{version}' has base address 0x{:X}, but the profile expects 0x{expected:X}: {path:?}",
+                    offsets.base_address()
+                );
+            }
+            db.insert(version, offsets);
         }
 
         Ok((Self { db }, graph))
@@ -214,12 +540,38 @@ impl OffsetLists {
     pub fn iter(&self) -> impl Iterator<Item = (&Version, &OffsetList)> {
         self.db.iter()
     }
+
+    /// Iterates over each pair of consecutive versions, ordered from oldest to newest.
+    pub fn adjacent_pairs(
+        &self,
+    ) -> impl Iterator<Item = ((&Version, &OffsetList), (&Version, &OffsetList))> {
+        self.db.iter().zip(self.db.iter().skip(1))
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::OffsetList;
-    use std::io::BufReader;
+    use super::{
+        Kind,
+        OffsetList,
+        OffsetLists,
+    };
+    use crate::{
+        common,
+        errors::ParseErrors,
+        graph::Graph,
+        manifest::Manifest,
+        pe::Executable,
+    };
+    use byteorder::{
+        ByteOrder,
+        LittleEndian,
+    };
+    use std::{
+        fs,
+        io::BufReader,
+        path::Path,
+    };
 
     #[test]
     fn test_base_address() -> anyhow::Result<()> {
@@ -228,8 +580,11 @@ mod tests {
 baseaddress	140000000
 "[..],
         );
-        let result = OffsetList::parse_base_address(&mut buffer)?;
-        assert_eq!(result, 0x140000000);
+        let result = OffsetList::parse_base_address(
+            &mut buffer,
+            &mut ParseErrors::default().file(Path::new("test")),
+        )?;
+        assert_eq!(result, 0x1_4000_0000);
         Ok(())
     }
 
@@ -249,8 +604,9 @@ func	140001180	140001187
         );
         let result = OffsetList::parse_generic_offsets(
             &mut buffer,
-            0x140000000,
+            0x1_4000_0000,
             OffsetList::FUNCTION_PATTERN,
+            &mut ParseErrors::default().file(Path::new("test")),
         )?
         .iter()
         .map(|x| x.0)
@@ -262,6 +618,61 @@ func	140001180	140001187
         Ok(())
     }
 
+    #[test]
+    fn test_functions() -> anyhow::Result<()> {
+        let mut buffer = BufReader::new(
+            &br"version	1
+func	140001000	14000100B
+func	140001060	14000106B
+func	140001080	140001083
+"[..],
+        );
+        let result = OffsetList::parse_functions(
+            &mut buffer,
+            0x1_4000_0000,
+            &mut ParseErrors::default().file(Path::new("test")),
+        )?
+        .iter()
+        .map(|x| (x.start.0, x.end.0, x.size()))
+        .collect::<Vec<_>>();
+        assert_eq!(
+            result,
+            [
+                (0x1000, 0x100B, 0xB),
+                (0x1060, 0x106B, 0xB),
+                (0x1080, 0x1083, 0x3)
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_lenient_functions() -> anyhow::Result<()> {
+        let mut buffer = BufReader::new(
+            &br"version	1
+func	140001000	14000100B
+func	14000106B	140001060
+func	1400010GG	140001083
+func	140001090	140001105
+"[..],
+        );
+        let mut errors = ParseErrors::lenient(2);
+        let result = OffsetList::parse_functions(
+            &mut buffer,
+            0x1_4000_0000,
+            &mut errors.file(Path::new("test")),
+        )?
+        .iter()
+        .map(|x| x.start.0)
+        .collect::<Vec<_>>();
+        assert_eq!(result, [0x1000, 0x1090]);
+        assert_eq!(
+            errors.lines(),
+            [(Path::new("test"), 3), (Path::new("test"), 4)]
+        );
+        Ok(())
+    }
+
     #[test]
     fn test_global_offsets() -> anyhow::Result<()> {
         let mut buffer = BufReader::new(
@@ -277,15 +688,16 @@ global	146A8F570
         );
         let result = OffsetList::parse_generic_offsets(
             &mut buffer,
-            0x140000000,
+            0x1_4000_0000,
             OffsetList::GLOBAL_PATTERN,
+            &mut ParseErrors::default().file(Path::new("test")),
         )?
         .iter()
         .map(|x| x.0)
         .collect::<Vec<_>>();
         assert_eq!(
             result,
-            [0x2C0F30C, 0x2C166DC, 0x2C17000, 0x6736290, 0x674C73B, 0x6A8C000, 0x6A8F570]
+            [0x2C0_F30C, 0x2C1_66DC, 0x2C1_7000, 0x673_6290, 0x674_C73B, 0x6A8_C000, 0x6A8_F570]
         );
         Ok(())
     }
@@ -304,18 +716,107 @@ name	146A8C000	TlsStart
 name	146A8F570	TlsEnd
 "[..],
         );
-        let result =
-            OffsetList::parse_generic_offsets(&mut buffer, 0x140000000, OffsetList::NAME_PATTERN)?
-                .iter()
-                .map(|x| x.0)
-                .collect::<Vec<_>>();
+        let result = OffsetList::parse_generic_offsets(
+            &mut buffer,
+            0x1_4000_0000,
+            OffsetList::NAME_PATTERN,
+            &mut ParseErrors::default().file(Path::new("test")),
+        )?
+        .iter()
+        .map(|x| x.0)
+        .collect::<Vec<_>>();
         assert_eq!(
             result,
             [
-                0x0001000, 0x0001080, 0x00015C0, 0x00015D0, 0x0002A70, 0x6737000, 0x6A8C000,
-                0x6A8F570,
+                0x0000_1000,
+                0x0000_1080,
+                0x0000_15C0,
+                0x0000_15D0,
+                0x0000_2A70,
+                0x673_7000,
+                0x6A8_C000,
+                0x6A8_F570,
             ]
         );
         Ok(())
     }
+
+    #[test]
+    fn test_names() -> anyhow::Result<()> {
+        let mut buffer = BufReader::new(
+            &br"version	1
+name	140001000	??0_Fac_node@std@@QEAA@PEAU01@PEAV_Facet_base@1@@Z	std::_Fac_node::_Fac_node(std::_Fac_node *,std::_Facet_base *)
+name	140001080	nullsub_4382
+name	140001100
+"[..],
+        );
+        let result = OffsetList::parse_names(
+            &mut buffer,
+            0x1_4000_0000,
+            &mut ParseErrors::default().file(Path::new("test")),
+        )?;
+        assert_eq!(result.len(), 3);
+        assert_eq!(result[0].0 .0, 0x1000);
+        assert_eq!(
+            result[0].1.as_deref(),
+            Some("std::_Fac_node::_Fac_node(std::_Fac_node *,std::_Facet_base *)")
+        );
+        assert_eq!(result[1].0 .0, 0x1080);
+        assert_eq!(result[1].1.as_deref(), Some("nullsub_4382"));
+        assert_eq!(result[2].0 .0, 0x1100);
+        assert_eq!(result[2].1, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_executable() -> anyhow::Result<()> {
+        let executable = Executable::sample();
+        let version = "1.10.163".parse()?;
+        let directory = Path::new("1.10.163");
+        let mut graph = Graph::default();
+        let offset_list =
+            OffsetList::from_executable(directory, &executable, version, &mut graph, &[])?;
+        assert!(!offset_list.has_idaexport());
+        assert_eq!(offset_list.base_address(), 0x1_4000_0000);
+
+        // the chained function is part of the first, and the relocation targets code
+        let offsets = offset_list
+            .iter()
+            .map(|(offset, mapping)| (offset.0, mapping.kind))
+            .collect::<Vec<_>>();
+        assert_eq!(offsets, [(0x1000, Kind::Function)]);
+        assert_eq!(offset_list.functions()[0].size(), 0x10);
+
+        let sections = [".pdata".to_owned()];
+        let offset_list =
+            OffsetList::from_executable(directory, &executable, version, &mut graph, &sections)?;
+        assert_eq!(offset_list.iter().count(), 0);
+
+        // a section which wraps around the end of the address space
+        let root = common::test_dir("from-executable");
+        let path = root.join("Fallout4.exe");
+        let mut bytes = Executable::sample_bytes();
+        LittleEndian::write_u32(&mut bytes[0x170 + 8..], 0xFFFF_FFFF);
+        fs::write(&path, bytes)?;
+        let executable = Executable::load(&path)?;
+        assert!(
+            OffsetList::from_executable(directory, &executable, version, &mut graph, &[]).is_err()
+        );
+        fs::remove_dir_all(root)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_mislabeled_executable() -> anyhow::Result<()> {
+        let root = common::test_dir("mislabeled-executable");
+        let directory = root.join("1.10.130");
+        common::write_idaexport(&directory, &[(0x1000, 0x1010)], &[], &[])?;
+        let manifest = Manifest::load(&root, None)?;
+        assert!(OffsetLists::parse_all(&manifest, &[], &mut ParseErrors::default()).is_ok());
+
+        fs::write(directory.join("Fallout4.exe"), Executable::sample_bytes())?;
+        assert!(OffsetLists::parse_all(&manifest, &[], &mut ParseErrors::default()).is_err());
+        fs::remove_dir_all(root)?;
+        Ok(())
+    }
 }
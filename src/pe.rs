@@ -0,0 +1,583 @@
+use crate::{
+    addrlib::AddressBins,
+    common::{
+        Offset,
+        Version,
+    },
+    errors::ParseErrors,
+    manifest::{
+        self,
+        Manifest,
+    },
+    offsets::{
+        Kind,
+        OffsetLists,
+    },
+    profile::Profile,
+};
+use anyhow::Context as _;
+use byteorder::{
+    ByteOrder as _,
+    LittleEndian,
+};
+use std::{
+    collections::BTreeSet,
+    fmt::Write as _,
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+pub struct Section {
+    pub name: String,
+    pub virtual_address: u32,
+    pub virtual_size: u32,
+    raw_offset: u32,
+    raw_size: u32,
+    characteristics: u32,
+}
+
+impl Section {
+    const CNT_CODE: u32 = 0x20;
+    const CNT_INITIALIZED_DATA: u32 = 0x40;
+    const CNT_UNINITIALIZED_DATA: u32 = 0x80;
+
+    pub fn contains(&self, offset: Offset) -> bool {
+        self.virtual_address <= offset.0 && offset.0 - self.virtual_address < self.virtual_size
+    }
+
+    pub fn is_data(&self) -> bool {
+        self.characteristics & Self::CNT_CODE == 0
+            && self.characteristics & (Self::CNT_INITIALIZED_DATA | Self::CNT_UNINITIALIZED_DATA)
+                != 0
+    }
+}
+
+/// An entry of the exception directory.
+pub struct RuntimeFunction {
+    pub begin: Offset,
+    pub end: Offset,
+    /// Whether the unwind info chains to that of another entry, in which case the entry describes
+    /// a part of a function rather than the start of one.
+    pub chained: bool,
+}
+
+/// A 64-bit PE image, as read from an executable on disk.
+pub struct Executable {
+    bytes: Vec<u8>,
+    image_base: u64,
+    size_of_image: u32,
+    sections: Vec<Section>,
+    /// The rva and size of each entry of the data directory.
+    directories: Vec<(u32, u32)>,
+}
+
+impl Executable {
+    const RESOURCE_DIRECTORY: usize = 2;
+    const EXCEPTION_DIRECTORY: usize = 3;
+    const BASE_RELOCATION_DIRECTORY: usize = 5;
+    const IMAGE_REL_BASED_DIR64: u16 = 10;
+    const RT_VERSION: u32 = 16;
+    const FIXED_FILE_INFO_SIGNATURE: u32 = 0xFEEF_04BD;
+    const UNW_FLAG_CHAININFO: u8 = 0x4;
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let bytes =
+            fs::read(path).with_context(|| format!("failed to read file: {}", path.display()))?;
+        Self::parse(bytes)
+            .with_context(|| format!("failed to parse executable: {}", path.display()))
+    }
+
+    fn parse(bytes: Vec<u8>) -> anyhow::Result<Self> {
+        if bytes.get(..2) != Some(b"MZ") {
+            anyhow::bail!("missing dos signature");
+        }
+        let nt_headers =
+            read_u32(&bytes, 0x3C).context("failed to read nt header offset")? as usize;
+        if bytes.get(nt_headers..nt_headers + 4) != Some(b"PE\0\0") {
+            anyhow::bail!("missing nt signature");
+        }
+
+        let file_header = nt_headers + 4;
+        let section_count =
+            read_u16(&bytes, file_header + 2).context("failed to read section count")?;
+        let optional_header_size =
+            read_u16(&bytes, file_header + 16).context("failed to read optional header size")?;
+
+        let optional_header = file_header + 20;
+        let magic = read_u16(&bytes, optional_header).context("failed to read magic")?;
+        if magic != 0x20B {
+            anyhow::bail!("unsupported optional header magic: 0x{magic:X}");
+        }
+        let image_base =
+            read_u64(&bytes, optional_header + 24).context("failed to read image base")?;
+        let size_of_image =
+            read_u32(&bytes, optional_header + 56).context("failed to read size of image")?;
+        let directory_count =
+            read_u32(&bytes, optional_header + 108).context("failed to read directory count")?;
+        let directories = (0..directory_count as usize)
+            .map(|i| {
+                let pos = optional_header + 112 + i * 8;
+                Ok((read_u32(&bytes, pos)?, read_u32(&bytes, pos + 4)?))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()
+            .context("failed to read data directories")?;
+
+        let section_table = optional_header + usize::from(optional_header_size);
+        let sections = (0..usize::from(section_count))
+            .map(|i| {
+                let pos = section_table + i * 40;
+                let name = bytes
+                    .get(pos..pos + 8)
+                    .context("section header is out of bounds")?;
+                let name = String::from_utf8_lossy(name)
+                    .trim_end_matches('\0')
+                    .to_owned();
+                Ok(Section {
+                    name,
+                    virtual_size: read_u32(&bytes, pos + 8)?,
+                    virtual_address: read_u32(&bytes, pos + 12)?,
+                    raw_size: read_u32(&bytes, pos + 16)?,
+                    raw_offset: read_u32(&bytes, pos + 20)?,
+                    characteristics: read_u32(&bytes, pos + 36)?,
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()
+            .context("failed to read section table")?;
+
+        Ok(Self {
+            bytes,
+            image_base,
+            size_of_image,
+            sections,
+            directories,
+        })
+    }
+
+    pub fn image_base(&self) -> u64 {
+        self.image_base
+    }
+
+    pub fn sections(&self) -> &[Section] {
+        &self.sections
+    }
+
+    pub fn section_containing(&self, offset: Offset) -> Option<&Section> {
+        self.sections.iter().find(|x| x.contains(offset))
+    }
+
+    /// Reads the given number of bytes at the given offset from the image base, provided they are
+    /// backed by the file.
+    fn read(&self, offset: Offset, len: usize) -> Option<&[u8]> {
+        let section = self.section_containing(offset)?;
+        let pos = offset.0 - section.virtual_address;
+        if pos as usize + len > section.raw_size as usize {
+            return None;
+        }
+        let start = section.raw_offset.checked_add(pos)? as usize;
+        self.bytes.get(start..start + len)
+    }
+
+    fn directory(&self, index: usize) -> Option<(Offset, usize)> {
+        self.directories
+            .get(index)
+            .filter(|(rva, size)| *rva != 0 && *size != 0)
+            .map(|(rva, size)| (Offset(*rva), *size as usize))
+    }
+
+    /// Reads every entry of the exception directory.
+    pub fn runtime_functions(&self) -> anyhow::Result<Vec<RuntimeFunction>> {
+        let Some((offset, size)) = self.directory(Self::EXCEPTION_DIRECTORY) else {
+            return Ok(Vec::new());
+        };
+        let bytes = self
+            .read(offset, size)
+            .context("exception directory is not backed by the file")?;
+        bytes
+            .chunks_exact(12)
+            .map(|entry| {
+                let begin = Offset(LittleEndian::read_u32(&entry[0..]));
+                let end = Offset(LittleEndian::read_u32(&entry[4..]));
+                let unwind_info = Offset(LittleEndian::read_u32(&entry[8..]) & !1);
+                let flags = self.read(unwind_info, 1).with_context(|| {
+                    format!("unwind info is not backed by the file: {unwind_info}")
+                })?[0]
+                    >> 3;
+                Ok(RuntimeFunction {
+                    begin,
+                    end,
+                    chained: flags & Self::UNW_FLAG_CHAININFO != 0,
+                })
+            })
+            .collect()
+    }
+
+    /// Reads the targets of every 64-bit base relocation, as offsets from the image base. Targets
+    /// which do not lie within the image are skipped.
+    pub fn relocation_targets(&self) -> anyhow::Result<Vec<Offset>> {
+        let Some((offset, size)) = self.directory(Self::BASE_RELOCATION_DIRECTORY) else {
+            return Ok(Vec::new());
+        };
+        let mut bytes = self
+            .read(offset, size)
+            .context("base relocation directory is not backed by the file")?;
+
+        let mut result = Vec::new();
+        while bytes.len() >= 8 {
+            let page = LittleEndian::read_u32(&bytes[0..]);
+            let block_size = LittleEndian::read_u32(&bytes[4..]) as usize;
+            let block = bytes
+                .get(8..block_size)
+                .with_context(|| format!("base relocation block is out of bounds: 0x{page:X}"))?;
+            for entry in block.chunks_exact(2) {
+                let entry = LittleEndian::read_u16(entry);
+                if entry >> 12 != Self::IMAGE_REL_BASED_DIR64 {
+                    continue;
+                }
+                let location = page
+                    .checked_add(u32::from(entry & 0xFFF))
+                    .map(Offset)
+                    .with_context(|| format!("base relocation is out of range: 0x{page:X}"))?;
+                let Some(target) = self.read(location, 8).map(LittleEndian::read_u64) else {
+                    continue;
+                };
+                if let Some(target) = target
+                    .checked_sub(self.image_base)
+                    .and_then(|x| u32::try_from(x).ok())
+                    .filter(|x| *x < self.size_of_image)
+                {
+                    result.push(Offset(target));
+                }
+            }
+            bytes = &bytes[block_size..];
+        }
+
+        result.sort();
+        result.dedup();
+        Ok(result)
+    }
+
+    /// The starts of all functions described by the exception directory.
+    pub fn function_starts(&self) -> anyhow::Result<BTreeSet<Offset>> {
+        Ok(self
+            .runtime_functions()?
+            .into_iter()
+            .filter(|x| !x.chained)
+            .map(|x| x.begin)
+            .collect())
+    }
+
+    /// The offset of the given position within the resource directory.
+    fn resource_offset(directory: Offset, pos: u32) -> anyhow::Result<Offset> {
+        directory
+            .0
+            .checked_add(pos)
+            .map(Offset)
+            .with_context(|| format!("resource directory position is out of range: 0x{pos:X}"))
+    }
+
+    /// Follows the first entry of the resource directory at the given position, matching the given
+    /// id if there is one, and returns the position of what it points to.
+    fn resource_entry(&self, directory: Offset, pos: u32, id: Option<u32>) -> anyhow::Result<u32> {
+        let header = self
+            .read(Self::resource_offset(directory, pos)?, 16)
+            .context("resource directory is not backed by the file")?;
+        let count = u32::from(LittleEndian::read_u16(&header[12..]))
+            + u32::from(LittleEndian::read_u16(&header[14..]));
+        for i in 0..count {
+            let entry_pos = pos
+                .checked_add(16 + i * 8)
+                .with_context(|| format!("resource directory is out of range: 0x{pos:X}"))?;
+            let entry = self
+                .read(Self::resource_offset(directory, entry_pos)?, 8)
+                .context("resource directory entry is not backed by the file")?;
+            let name = LittleEndian::read_u32(&entry[0..]);
+            if id.is_none_or(|x| x == name) {
+                return Ok(LittleEndian::read_u32(&entry[4..]) & 0x7FFF_FFFF);
+            }
+        }
+        anyhow::bail!("found no matching resource directory entry")
+    }
+
+    /// Reads the file version from the `VS_FIXEDFILEINFO` within the version resource.
+    pub fn version(&self) -> anyhow::Result<Version> {
+        let (directory, _) = self
+            .directory(Self::RESOURCE_DIRECTORY)
+            .context("executable has no resources")?;
+        let pos = self
+            .resource_entry(directory, 0, Some(Self::RT_VERSION))
+            .context("failed to find version resource")?;
+        let pos = self.resource_entry(directory, pos, None)?;
+        let pos = self.resource_entry(directory, pos, None)?;
+        let data = self
+            .read(Self::resource_offset(directory, pos)?, 8)
+            .context("resource data entry is not backed by the file")?;
+        let data = self
+            .read(
+                Offset(LittleEndian::read_u32(&data[0..])),
+                LittleEndian::read_u32(&data[4..]) as usize,
+            )
+            .context("version resource is not backed by the file")?;
+
+        let info = (0..data.len().saturating_sub(16))
+            .step_by(4)
+            .map(|x| &data[x..])
+            .find(|x| LittleEndian::read_u32(x) == Self::FIXED_FILE_INFO_SIGNATURE)
+            .context("found no fixed file info within the version resource")?;
+        let most = LittleEndian::read_u32(&info[8..]);
+        let least = LittleEndian::read_u32(&info[12..]);
+        #[allow(clippy::cast_possible_truncation)]
+        Ok([
+            (most >> 16) as u16,
+            most as u16,
+            (least >> 16) as u16,
+            least as u16,
+        ]
+        .into())
+    }
+
+    /// A small executable with a `.text` and a `.pdata` section, two functions of which the second
+    /// is chained to the first, a version resource and a single base relocation.
+    #[cfg(test)]
+    pub fn sample() -> Self {
+        Self::parse(Self::sample_bytes()).expect("failed to parse sample executable")
+    }
+
+    /// The file contents of [`Self::sample`].
+    #[cfg(test)]
+    pub fn sample_bytes() -> Vec<u8> {
+        let mut bytes = vec![0; 0x400];
+        bytes[..2].copy_from_slice(b"MZ");
+        LittleEndian::write_u32(&mut bytes[0x3C..], 0x40);
+        bytes[0x40..0x44].copy_from_slice(b"PE\0\0");
+        LittleEndian::write_u16(&mut bytes[0x46..], 2);
+        LittleEndian::write_u16(&mut bytes[0x54..], 0xF0);
+        LittleEndian::write_u16(&mut bytes[0x58..], 0x20B);
+        LittleEndian::write_u64(&mut bytes[0x58 + 24..], 0x1_4000_0000);
+        LittleEndian::write_u32(&mut bytes[0x58 + 56..], 0x3000);
+        LittleEndian::write_u32(&mut bytes[0x58 + 108..], 16);
+        LittleEndian::write_u32(&mut bytes[0x58 + 112 + 3 * 8..], 0x2000);
+        LittleEndian::write_u32(&mut bytes[0x58 + 112 + 3 * 8 + 4..], 24);
+
+        let mut section = |pos: usize, name: &[u8], rva: u32, raw: u32, characteristics: u32| {
+            bytes[pos..pos + name.len()].copy_from_slice(name);
+            LittleEndian::write_u32(&mut bytes[pos + 8..], 0x100);
+            LittleEndian::write_u32(&mut bytes[pos + 12..], rva);
+            LittleEndian::write_u32(&mut bytes[pos + 16..], 0x100);
+            LittleEndian::write_u32(&mut bytes[pos + 20..], raw);
+            LittleEndian::write_u32(&mut bytes[pos + 36..], characteristics);
+        };
+        section(0x148, b".text", 0x1000, 0x200, 0x6000_0020);
+        section(0x170, b".pdata", 0x2000, 0x300, 0x4000_0040);
+
+        for (i, (begin, end, unwind_info)) in [(0x1000, 0x1010, 0x2018), (0x1010, 0x1020, 0x2020)]
+            .into_iter()
+            .enumerate()
+        {
+            let pos = 0x300 + i * 12;
+            LittleEndian::write_u32(&mut bytes[pos..], begin);
+            LittleEndian::write_u32(&mut bytes[pos + 4..], end);
+            LittleEndian::write_u32(&mut bytes[pos + 8..], unwind_info);
+        }
+        bytes[0x318] = 0x01;
+        bytes[0x320] = 0x21;
+
+        LittleEndian::write_u32(&mut bytes[0x58 + 112 + 2 * 8..], 0x2040);
+        LittleEndian::write_u32(&mut bytes[0x58 + 112 + 2 * 8 + 4..], 0x98);
+        for (pos, id, target) in [
+            (0x340, 16, 0x8000_0018),
+            (0x358, 1, 0x8000_0030),
+            (0x370, 0x409, 0x48),
+        ] {
+            LittleEndian::write_u16(&mut bytes[pos + 14..], 1);
+            LittleEndian::write_u32(&mut bytes[pos + 16..], id);
+            LittleEndian::write_u32(&mut bytes[pos + 20..], target);
+        }
+        LittleEndian::write_u32(&mut bytes[0x388..], 0x2098);
+        LittleEndian::write_u32(&mut bytes[0x38C..], 0x40);
+        LittleEndian::write_u32(&mut bytes[0x3C0..], 0xFEEF_04BD);
+        LittleEndian::write_u32(&mut bytes[0x3C8..], 0x0001_000A);
+        LittleEndian::write_u32(&mut bytes[0x3CC..], 0x00A3_0000);
+
+        LittleEndian::write_u32(&mut bytes[0x58 + 112 + 5 * 8..], 0x20E0);
+        LittleEndian::write_u32(&mut bytes[0x58 + 112 + 5 * 8 + 4..], 12);
+        LittleEndian::write_u32(&mut bytes[0x3E0..], 0x2000);
+        LittleEndian::write_u32(&mut bytes[0x3E4..], 12);
+        LittleEndian::write_u16(&mut bytes[0x3E8..], 0xA0F0);
+        LittleEndian::write_u64(&mut bytes[0x3F0..], 0x1_4000_1008);
+        bytes
+    }
+
+    /// Finds the executable within the given directory, if there is one.
+    pub fn find(directory: &Path) -> anyhow::Result<Option<PathBuf>> {
+        for entry in fs::read_dir(directory)
+            .with_context(|| format!("failed to read directory: {}", directory.display()))?
+        {
+            let path = entry
+                .with_context(|| format!("error while reading directory: {}", directory.display()))?
+                .path();
+            if path
+                .extension()
+                .is_some_and(|x| x.eq_ignore_ascii_case("exe"))
+            {
+                return Ok(Some(path));
+            }
+        }
+        Ok(None)
+    }
+}
+
+fn read_u16(bytes: &[u8], pos: usize) -> anyhow::Result<u16> {
+    let bytes = bytes.get(pos..pos + 2).context("read is out of bounds")?;
+    Ok(LittleEndian::read_u16(bytes))
+}
+
+fn read_u32(bytes: &[u8], pos: usize) -> anyhow::Result<u32> {
+    let bytes = bytes.get(pos..pos + 4).context("read is out of bounds")?;
+    Ok(LittleEndian::read_u32(bytes))
+}
+
+fn read_u64(bytes: &[u8], pos: usize) -> anyhow::Result<u64> {
+    let bytes = bytes.get(pos..pos + 8).context("read is out of bounds")?;
+    Ok(LittleEndian::read_u64(bytes))
+}
+
+/// Fails if the file version of the given executable, loaded from the given path, differs from
+/// the version of the directory it was found in.
+pub fn check_version(executable: &Executable, path: &Path, version: Version) -> anyhow::Result<()> {
+    let found = executable
+        .version()
+        .with_context(|| format!("failed to read version from executable: {}", path.display()))?;
+    if found != version {
+        anyhow::bail!(
+            "executable has version '{found}', but its directory has version '{version}': {path:?}"
+        );
+    }
+    Ok(())
+}
+
+/// Prints the file version of the given executable.
+pub fn print_version(path: &Path) -> anyhow::Result<()> {
+    let version = Executable::load(path)?
+        .version()
+        .with_context(|| format!("failed to read version from executable: {}", path.display()))?;
+    println!("{version}");
+    Ok(())
+}
+
+/// Checks every offset in the address bin of the given version against the executable of that
+/// version: every offset must lie within a section of the image, every function must start an
+/// entry of the exception directory, and every global must lie within a data section.
+pub fn verify_executable(
+    root_dir: &Path,
+    version: Version,
+    executable: Option<&Path>,
+    output: Option<&Path>,
+    profile: Option<Profile>,
+    module: &str,
+) -> anyhow::Result<()> {
+    let manifest = Manifest::load(root_dir, profile)?.module(module);
+    let (offset_lists, _) =
+        OffsetLists::parse_matching(&manifest, &[], &mut ParseErrors::default(), |x| {
+            x == version
+        })
+        .context("failed to parse offsets")?;
+    let offset_list = offset_lists.get(version);
+    let address_bins = AddressBins::parse_all(&manifest, &mut ParseErrors::default())
+        .context("failed to parse address bins")?;
+    let address_bin = address_bins
+        .get(version)
+        .with_context(|| format!("found no address bin for version '{version}'"))?;
+
+    let path = match executable {
+        Some(x) => x.to_owned(),
+        None => offset_list
+            .map(|x| Executable::find(x.directory()))
+            .transpose()?
+            .flatten()
+            .with_context(|| format!("found no executable for version '{version}'"))?,
+    };
+    println!("parsing executable...");
+    let executable = Executable::load(&path)?;
+    if let Some(offset_list) = offset_list {
+        if offset_list.base_address() != executable.image_base() {
+            println!(
+                "\texecutable has image base 0x{:X}, but the offsets are relative to 0x{:X}",
+                executable.image_base(),
+                offset_list.base_address()
+            );
+        }
+    }
+    let function_starts = executable
+        .function_starts()
+        .context("failed to read exception directory")?;
+
+    println!("verifying offsets...");
+    let mut result = String::new();
+    let mut violations = 0usize;
+    for (id, offset) in address_bin.iter() {
+        let kind = offset_list.and_then(|x| x.get(*offset)).map(|x| x.kind);
+        let violation = match executable.section_containing(*offset) {
+            _ if offset.0 >= executable.size_of_image => {
+                Some("lies outside of the image".to_owned())
+            }
+            None => Some("does not lie within any section".to_owned()),
+            Some(_) if kind == Some(Kind::Function) && !function_starts.contains(offset) => Some(
+                "is a function, but does not start an entry of the exception directory".to_owned(),
+            ),
+            Some(section) if kind == Some(Kind::Global) && !section.is_data() => Some(format!(
+                "is a global, but lies within a section which is not data: {}",
+                section.name
+            )),
+            Some(_) => None,
+        };
+        if let Some(violation) = violation {
+            violations += 1;
+            writeln!(result, "{id}\t{offset}\t{violation}")
+                .expect("writing to a string can not fail");
+        }
+    }
+    println!("\tfound {violations} violations");
+
+    let path = output.map_or_else(
+        || root_dir.join(manifest::report_name(module, "verify", version)),
+        Path::to_path_buf,
+    );
+    fs::write(&path, result).with_context(|| format!("failed to write file: {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Executable;
+    use crate::common::Offset;
+
+    #[test]
+    fn test_executable() -> anyhow::Result<()> {
+        let executable = Executable::sample();
+        assert_eq!(executable.image_base(), 0x1_4000_0000);
+        let sections = executable
+            .sections()
+            .iter()
+            .map(|x| (x.name.as_str(), x.is_data()))
+            .collect::<Vec<_>>();
+        assert_eq!(sections, [(".text", false), (".pdata", true)]);
+        let starts = executable
+            .function_starts()?
+            .into_iter()
+            .map(|x| x.0)
+            .collect::<Vec<_>>();
+        assert_eq!(starts, [0x1000]);
+        assert!(executable.read(Offset(0x10F0), 0x20).is_none());
+        assert!(executable.version()? == "1.10.163.0".parse()?);
+        let targets = executable
+            .relocation_targets()?
+            .into_iter()
+            .map(|x| x.0)
+            .collect::<Vec<_>>();
+        assert_eq!(targets, [0x1008]);
+        assert!(Executable::resource_offset(Offset(0xFFFF_FFF0), 0x20).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,94 @@
+use clap::ValueEnum;
+use serde::Deserialize;
+
+/// The game an address library is generated for.
+#[derive(Clone, Copy, Default, Deserialize, PartialEq, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Profile {
+    /// `version-*.bin`, flat
+    #[default]
+    Fallout4,
+
+    /// `version-*.bin`, compressed (format 1)
+    SkyrimSe,
+
+    /// `versionlib-*.bin`, compressed (format 2)
+    SkyrimAe,
+
+    /// `version-*.csv`
+    SkyrimVr,
+
+    /// `versionlib-*.bin`, compressed (format 2)
+    Starfield,
+}
+
+/// How the mappings of an address bin are laid out on disk.
+#[derive(Clone, Copy)]
+pub enum Layout {
+    /// A `u64` count, followed by a `u64` id and `u64` offset for every mapping.
+    Flat,
+
+    /// A header carrying the version, the name of the executable and the pointer size, followed by
+    /// every mapping encoded as a delta from the one before it.
+    Compressed {
+        format: i32,
+        executable: &'static str,
+    },
+
+    /// A csv file of `id,offset` rows, the first of which carries the count and the version.
+    Csv,
+}
+
+impl Profile {
+    pub fn layout(self) -> Layout {
+        match self {
+            Self::Fallout4 => Layout::Flat,
+            Self::SkyrimSe => Layout::Compressed {
+                format: 1,
+                executable: "SkyrimSE.exe",
+            },
+            Self::SkyrimAe => Layout::Compressed {
+                format: 2,
+                executable: "SkyrimSE.exe",
+            },
+            Self::SkyrimVr => Layout::Csv,
+            Self::Starfield => Layout::Compressed {
+                format: 2,
+                executable: "Starfield.exe",
+            },
+        }
+    }
+
+    /// The prefix of the file name of an address bin, which is followed by the version.
+    pub fn file_prefix(self) -> &'static str {
+        match self {
+            Self::Fallout4 | Self::SkyrimSe | Self::SkyrimVr => "version",
+            Self::SkyrimAe | Self::Starfield => "versionlib",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self.layout() {
+            Layout::Flat | Layout::Compressed { .. } => "bin",
+            Layout::Csv => "csv",
+        }
+    }
+
+    /// The address the executable is expected to be loaded at, which every address within the
+    /// idaexport files and the diffs is relative to.
+    pub fn base_address(self) -> u64 {
+        match self {
+            Self::Fallout4 | Self::SkyrimSe | Self::SkyrimAe | Self::SkyrimVr | Self::Starfield => {
+                0x1_4000_0000
+            }
+        }
+    }
+
+    pub fn pointer_size(self) -> u32 {
+        match self {
+            Self::Fallout4 | Self::SkyrimSe | Self::SkyrimAe | Self::SkyrimVr | Self::Starfield => {
+                8
+            }
+        }
+    }
+}
@@ -0,0 +1,225 @@
+use crate::{
+    addrlib::AddressBins,
+    common::{
+        Id,
+        Offset,
+    },
+    errors::ParseErrors,
+    manifest::{
+        self,
+        Manifest,
+    },
+    offsets::OffsetLists,
+    profile::Profile,
+};
+use anyhow::Context as _;
+use regex_lite::Regex;
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    fs::{
+        self,
+        File,
+    },
+    io::{
+        BufRead,
+        BufReader,
+    },
+    path::Path,
+};
+
+/// A well-known id, and an optional label describing what it refers to.
+struct Watch {
+    id: Id,
+    label: Option<String>,
+}
+
+/// Parses a list of ids, one per line, each optionally followed by a label. Blank lines and lines
+/// starting with `#` are ignored.
+fn parse_watch_list<R: BufRead>(src: &mut R) -> anyhow::Result<Vec<Watch>> {
+    let pattern = Regex::new(r"^(\d+)(?:\s+(.*))?$").context("failed to build watch pattern")?;
+    let mut result = Vec::new();
+    for (i, line) in src.lines().enumerate() {
+        let line = line.context("failed to read watch list")?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let captures = pattern
+            .captures(line)
+            .with_context(|| format!("failed to match watch pattern on line {}", i + 1))?;
+        let id = captures[1]
+            .parse::<u64>()
+            .ok()
+            .and_then(|x| Id::try_from(x).ok())
+            .with_context(|| format!("failed to parse id on line {}: {}", i + 1, &captures[1]))?;
+        let label = captures
+            .get(2)
+            .map(|x| x.as_str().trim().to_owned())
+            .filter(|x| !x.is_empty());
+        result.push(Watch { id, label });
+    }
+    Ok(result)
+}
+
+fn load_watch_list(path: &Path) -> anyhow::Result<Vec<Watch>> {
+    let file =
+        File::open(path).with_context(|| format!("failed to open file: {}", path.display()))?;
+    parse_watch_list(&mut BufReader::new(file))
+        .with_context(|| format!("failed to parse watch list: {}", path.display()))
+}
+
+/// Describes how a watched id changed between the previous and the current version, or `None` if
+/// it did not change.
+fn watch_status(previous: Option<&Offset>, current: Option<&Offset>) -> Option<String> {
+    match (previous, current) {
+        (Some(previous), Some(current)) if previous != current => {
+            Some(format!("moved from {previous} to {current}"))
+        }
+        (Some(_), Some(_)) => None,
+        (Some(previous), None) => Some(format!("disappeared, was at {previous}")),
+        (None, Some(current)) => Some(format!("appeared at {current}")),
+        (None, None) => Some("present in neither version".to_owned()),
+    }
+}
+
+/// Writes a summary of the changes between the newest address bin and the one before it.
+pub fn write_release_notes(
+    root_dir: &Path,
+    watch_list: Option<&Path>,
+    output: Option<&Path>,
+    profile: Option<Profile>,
+    module: &str,
+) -> anyhow::Result<()> {
+    let watch_list = watch_list
+        .map(load_watch_list)
+        .transpose()?
+        .unwrap_or_default();
+
+    let manifest = Manifest::load(root_dir, profile)?.module(module);
+    let address_bins = AddressBins::parse_all(&manifest, &mut ParseErrors::default())
+        .context("failed to parse address bins")?;
+    let mut newest = address_bins.iter().rev();
+    let (Some((version, bin)), Some((previous_version, previous_bin))) =
+        (newest.next(), newest.next())
+    else {
+        anyhow::bail!("at least two address bins are required to write release notes");
+    };
+    let (offset_lists, _) =
+        OffsetLists::parse_matching(&manifest, &[], &mut ParseErrors::default(), |x| {
+            x == *version || x == *previous_version
+        })
+        .context("failed to parse offsets")?;
+
+    println!("writing release notes...");
+    let mappings = bin.iter().copied().collect::<BTreeMap<Id, Offset>>();
+    let previous_mappings = previous_bin
+        .iter()
+        .copied()
+        .collect::<BTreeMap<Id, Offset>>();
+    let carried = mappings
+        .iter()
+        .filter_map(|(id, offset)| Some((*id, previous_mappings.get(id)?, offset)))
+        .collect::<Vec<_>>();
+    let dropped = previous_mappings
+        .iter()
+        .filter(|(id, _)| !mappings.contains_key(id))
+        .collect::<Vec<_>>();
+    let added = mappings
+        .iter()
+        .filter(|(id, _)| !previous_mappings.contains_key(id))
+        .collect::<Vec<_>>();
+
+    let mut result = String::new();
+    macro_rules! line {
+        ($($arg:tt)*) => {
+            writeln!(result, $($arg)*).expect("writing to a string can not fail")
+        };
+    }
+
+    line!("release notes for {version}, compared to {previous_version}");
+    line!();
+    line!("{} ids carried over", carried.len());
+    line!();
+    line!("{} ids dropped:", dropped.len());
+    for (id, offset) in dropped {
+        line!("\t{id}\t{offset}");
+    }
+    line!();
+    line!("{} ids added:", added.len());
+    for (id, offset) in added {
+        line!("\t{id}\t{offset}");
+    }
+
+    line!();
+    if let (Some(offset_list), Some(previous_offset_list)) = (
+        offset_lists.get(*version),
+        offset_lists.get(*previous_version),
+    ) {
+        let resized = carried
+            .iter()
+            .filter_map(|(id, previous_offset, offset)| {
+                let name = offset_list
+                    .name(**offset)
+                    .or_else(|| previous_offset_list.name(**previous_offset))?;
+                let size = offset_list.function(**offset)?.size();
+                let previous_size = previous_offset_list.function(**previous_offset)?.size();
+                (size != previous_size).then_some((id, name, previous_size, size))
+            })
+            .collect::<Vec<_>>();
+        line!("{} named functions changed size:", resized.len());
+        for (id, name, previous_size, size) in resized {
+            line!("\t{id}\t{name}\t0x{previous_size:X} -> 0x{size:X}");
+        }
+    } else {
+        println!("\tfound no offset info for both versions, skipping function sizes");
+        line!("named functions changed size: unknown, missing offset info");
+    }
+
+    if !watch_list.is_empty() {
+        line!();
+        line!("watch list:");
+        for watch in &watch_list {
+            let status = watch_status(previous_mappings.get(&watch.id), mappings.get(&watch.id));
+            if let Some(status) = status {
+                let label = watch.label.as_deref().unwrap_or("");
+                line!("\t{}\t{label}\t{status}", watch.id);
+            }
+        }
+    }
+
+    let path = output.map_or_else(
+        || root_dir.join(manifest::report_name(module, "release-notes", *version)),
+        Path::to_path_buf,
+    );
+    fs::write(&path, result).with_context(|| format!("failed to write file: {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufReader;
+
+    #[test]
+    fn test_watch_list() -> anyhow::Result<()> {
+        let mut buffer = BufReader::new(
+            &br"# player singleton
+517014	PlayerCharacter::Singleton
+
+  # no label
+11045
+"[..],
+        );
+        let result = super::parse_watch_list(&mut buffer)?
+            .into_iter()
+            .map(|x| (x.id.get(), x.label))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            result,
+            [
+                (517_014, Some("PlayerCharacter::Singleton".to_owned())),
+                (11045, None)
+            ]
+        );
+        Ok(())
+    }
+}
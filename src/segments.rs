@@ -0,0 +1,181 @@
+use crate::{
+    common::Offset,
+    errors::FileErrors,
+    graph::Graph,
+    offsets::{
+        OffsetList,
+        OffsetLists,
+    },
+};
+use std::{
+    collections::{
+        BTreeMap,
+        HashMap,
+    },
+    io::BufRead,
+};
+
+pub struct Segment {
+    pub start: Offset,
+    pub end: Offset,
+    pub name: String,
+}
+
+impl Segment {
+    pub fn contains(&self, offset: Offset) -> bool {
+        self.start <= offset && offset < self.end
+    }
+}
+
+#[derive(Default)]
+pub struct SegmentList {
+    segments: Vec<Segment>,
+}
+
+impl FromIterator<Segment> for SegmentList {
+    fn from_iter<T: IntoIterator<Item = Segment>>(iter: T) -> Self {
+        let mut segments = iter.into_iter().collect::<Vec<_>>();
+        segments.sort_by_key(|x| x.start);
+        Self { segments }
+    }
+}
+
+impl SegmentList {
+    const SEGMENT_PATTERN: &'static str = r"segment\t([\dA-Fa-f]+)\t([\dA-Fa-f]+)\t([^\t\r\n]+)";
+
+    pub fn parse<R: BufRead>(
+        idaexport: &mut R,
+        base_address: u64,
+        errors: &mut FileErrors,
+    ) -> anyhow::Result<Self> {
+        let mut segments =
+            OffsetList::parse_generic(idaexport, Self::SEGMENT_PATTERN, errors, |captures| {
+                let start = OffsetList::parse_offset(base_address, &captures[1])?;
+                let end = OffsetList::parse_offset(base_address, &captures[2])?;
+                if end < start {
+                    anyhow::bail!("segment ends before it starts: {}", &captures[0]);
+                }
+                Ok(Segment {
+                    start: Offset(start),
+                    end: Offset(end),
+                    name: captures[3].trim().to_owned(),
+                })
+            })?;
+        segments.sort_by_key(|x| x.start);
+        Ok(Self { segments })
+    }
+
+    pub fn find(&self, offset: Offset) -> Option<&Segment> {
+        let pos = self.segments.partition_point(|x| x.start <= offset);
+        pos.checked_sub(1)
+            .map(|x| &self.segments[x])
+            .filter(|x| x.contains(offset))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Segment> {
+        self.segments.iter()
+    }
+}
+
+/// Prints, for every version and segment, how many offsets received an id and how many of those
+/// ids are shared with at least one other version.
+pub fn print_coverage(graph: &Graph, offset_lists: &OffsetLists) {
+    println!("id coverage per segment:");
+
+    let mut id_counts = HashMap::new();
+    for (_, offset_list) in offset_lists.iter() {
+        for (_, mapping) in offset_list.iter() {
+            *id_counts.entry(graph.get(mapping.ix)).or_insert(0usize) += 1;
+        }
+    }
+
+    for (version, offset_list) in offset_lists.iter() {
+        println!("\t{version}:");
+        let mut coverage: BTreeMap<&str, (usize, usize)> = offset_list
+            .segments()
+            .iter()
+            .map(|x| (x.name.as_str(), (0, 0)))
+            .collect();
+        for (offset, mapping) in offset_list.iter() {
+            let name = offset_list
+                .segments()
+                .find(*offset)
+                .map_or("<none>", |x| x.name.as_str());
+            let entry = coverage.entry(name).or_default();
+            entry.0 += 1;
+            if id_counts[&graph.get(mapping.ix)] > 1 {
+                entry.1 += 1;
+            }
+        }
+        for (name, (total, shared)) in coverage {
+            #[allow(clippy::cast_precision_loss)]
+            let percent = if total == 0 {
+                0.0
+            } else {
+                shared as f64 / total as f64 * 100.0
+            };
+            println!("\t\t{name}: {shared}/{total} shared ({percent:.3}%)");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SegmentList;
+    use crate::{
+        common::Offset,
+        errors::ParseErrors,
+    };
+    use std::{
+        io::BufReader,
+        path::Path,
+    };
+
+    #[test]
+    fn test_segments() -> anyhow::Result<()> {
+        let mut buffer = BufReader::new(
+            &br"version	1
+segment	140001000	142C0B000	.text
+segment	142C0C000	142C0D000	.interpr
+segment	142C17000	142C18670	.idata
+segment	142C18670	1436CB000	.rdata
+segment	1436CB000	146737000	.data
+"[..],
+        );
+        let result = SegmentList::parse(
+            &mut buffer,
+            0x1_4000_0000,
+            &mut ParseErrors::default().file(Path::new("test")),
+        )?;
+        assert_eq!(
+            result
+                .iter()
+                .map(|x| (x.start.0, x.end.0, x.name.as_str()))
+                .collect::<Vec<_>>(),
+            [
+                (0x1000, 0x2C0_B000, ".text"),
+                (0x2C0_C000, 0x2C0_D000, ".interpr"),
+                (0x2C1_7000, 0x2C1_8670, ".idata"),
+                (0x2C1_8670, 0x36C_B000, ".rdata"),
+                (0x36C_B000, 0x673_7000, ".data"),
+            ]
+        );
+        assert_eq!(
+            result.find(Offset(0x1000)).map(|x| x.name.as_str()),
+            Some(".text")
+        );
+        assert_eq!(
+            result.find(Offset(0x2C0_B800)).map(|x| x.name.as_str()),
+            None
+        );
+        assert_eq!(
+            result.find(Offset(0x2C1_8670)).map(|x| x.name.as_str()),
+            Some(".rdata")
+        );
+        assert_eq!(
+            result.find(Offset(0x673_7000)).map(|x| x.name.as_str()),
+            None
+        );
+        Ok(())
+    }
+}
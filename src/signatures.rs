@@ -0,0 +1,270 @@
+use crate::{
+    addrlib::AddressBins,
+    asm::AsmList,
+    common::{
+        Id,
+        Offset,
+        Version,
+    },
+    errors::ParseErrors,
+    manifest::{
+        self,
+        Manifest,
+    },
+    offsets::OffsetLists,
+    profile::Profile,
+    xrefs::XrefList,
+};
+use anyhow::Context as _;
+use std::{
+    fmt::Write as _,
+    fs,
+    path::Path,
+};
+
+/// An IDA-style byte pattern, where `None` matches any byte.
+pub struct Signature(Vec<Option<u8>>);
+
+impl Signature {
+    pub fn to_ida_string(&self) -> String {
+        let mut result = String::new();
+        for (i, byte) in self.0.iter().enumerate() {
+            if i != 0 {
+                result.push(' ');
+            }
+            match byte {
+                Some(x) => write!(result, "{x:02X}").expect("writing to a string can not fail"),
+                None => result.push('?'),
+            }
+        }
+        result
+    }
+}
+
+/// A flat copy of the `.text` segment, rebuilt from the exported instructions.
+struct Image {
+    start: Offset,
+    bytes: Vec<u8>,
+    wildcards: Vec<bool>,
+    /// For every pair of leading bytes, the positions within the image at which it occurs.
+    index: Vec<Vec<u32>>,
+}
+
+impl Image {
+    /// How many positions may share the prefix of a pattern before they are copied out of the
+    /// index to be narrowed down in place.
+    const MAX_COPIED_CANDIDATES: usize = 0x1000;
+
+    fn new(asm_list: &AsmList, start: Offset, end: Offset) -> Self {
+        let len = (end.0 - start.0) as usize;
+        let mut bytes = vec![0; len];
+        let mut wildcards = vec![true; len];
+        for instruction in asm_list.iter() {
+            if instruction.offset < start || instruction.offset >= end {
+                continue;
+            }
+            let pos = (instruction.offset.0 - start.0) as usize;
+            for (i, byte) in instruction.bytes.iter().enumerate() {
+                if let Some(x) = bytes.get_mut(pos + i) {
+                    *x = *byte;
+                    wildcards[pos + i] = instruction.is_wildcard(i);
+                }
+            }
+        }
+        Self::from_bytes(start, bytes, wildcards)
+    }
+
+    fn from_bytes(start: Offset, bytes: Vec<u8>, wildcards: Vec<bool>) -> Self {
+        let len = bytes.len();
+        let mut index = vec![Vec::new(); 0x10000];
+        for pos in 0..len.saturating_sub(1) {
+            let key = usize::from(bytes[pos]) << 8 | usize::from(bytes[pos + 1]);
+            #[allow(clippy::cast_possible_truncation)]
+            index[key].push(pos as u32);
+        }
+
+        Self {
+            start,
+            bytes,
+            wildcards,
+            index,
+        }
+    }
+
+    /// Finds the shortest pattern starting at the given function which matches nowhere else within
+    /// the image, without extending past the end of the function or the maximum length.
+    fn signature(&self, function: Offset, end: Offset, max_len: usize) -> Option<Signature> {
+        let pos = function.0.checked_sub(self.start.0)? as usize;
+        let end =
+            ((end.0.checked_sub(self.start.0)? as usize).min(self.bytes.len())).min(pos + max_len);
+        if end <= pos + 1 || self.wildcards[pos] {
+            return None;
+        }
+
+        let pattern = (pos..end)
+            .map(|i| (!self.wildcards[i]).then_some(self.bytes[i]))
+            .collect::<Vec<_>>();
+        let first = usize::from(self.bytes[pos]) << 8;
+        let lists = match pattern[1] {
+            Some(x) => vec![&self.index[first | usize::from(x)][..]],
+            None => (0..=0xFF).map(|x| &self.index[first | x][..]).collect(),
+        };
+
+        let unique = |len: usize| {
+            let mut pattern = pattern[..len].to_vec();
+            while pattern.last().is_some_and(Option::is_none) {
+                pattern.pop();
+            }
+            Signature(pattern)
+        };
+        // the positions sharing a common prefix can number in the millions, so they are only
+        // copied out of the index once few enough of them are left
+        let mut candidates: Option<Vec<u32>> = None;
+        for len in 2..=pattern.len() {
+            let byte = pattern[len - 1];
+            if len > 2 && byte.is_none() {
+                continue;
+            }
+            if let Some(candidates) = &mut candidates {
+                candidates.retain(|x| self.bytes.get(*x as usize + len - 1) == byte.as_ref());
+            } else {
+                let matches = |x: &&u32| {
+                    pattern[2..len].iter().enumerate().all(|(i, byte)| {
+                        byte.is_none_or(|byte| self.bytes.get(**x as usize + 2 + i) == Some(&byte))
+                    })
+                };
+                let narrowed = lists.iter().flat_map(|x| x.iter()).filter(matches);
+                if narrowed.clone().nth(Self::MAX_COPIED_CANDIDATES).is_some() {
+                    continue;
+                }
+                candidates = Some(narrowed.copied().collect());
+            }
+            if candidates.as_ref().is_some_and(|x| x.len() == 1) {
+                return Some(unique(len));
+            }
+        }
+
+        None
+    }
+}
+
+/// Writes a unique byte signature for every function id within the address bin of the given
+/// version.
+pub fn write_signatures(
+    root_dir: &Path,
+    version: Version,
+    output: Option<&Path>,
+    max_len: usize,
+    profile: Option<Profile>,
+    module: &str,
+) -> anyhow::Result<()> {
+    let manifest = Manifest::load(root_dir, profile)?.module(module);
+    let mut errors = ParseErrors::default();
+    let (offset_lists, _) =
+        OffsetLists::parse_matching(&manifest, &[], &mut errors, |x| x == version)
+            .context("failed to parse offsets")?;
+    let offset_list = offset_lists
+        .get(version)
+        .with_context(|| format!("found no offset info for version '{version}'"))?;
+    let address_bins =
+        AddressBins::parse_all(&manifest, &mut errors).context("failed to parse address bins")?;
+    let address_bin = address_bins
+        .get(version)
+        .with_context(|| format!("found no address bin for version '{version}'"))?;
+
+    println!("building image...");
+    let text = offset_list
+        .segments()
+        .iter()
+        .find(|x| x.name == ".text")
+        .context("found no .text segment")?;
+    let mut asm_list =
+        AsmList::load(offset_list, &mut errors).context("failed to load instructions")?;
+    let xrefs = XrefList::load(offset_list, &mut errors).context("failed to load xrefs")?;
+    asm_list.mask_xrefs(&xrefs, offset_list.base_address());
+    let image = Image::new(&asm_list, text.start, text.end);
+
+    println!("generating signatures...");
+    let mut functions = address_bin
+        .iter()
+        .filter(|(_, offset)| offset_list.is_function(*offset))
+        .copied()
+        .collect::<Vec<(Id, Offset)>>();
+    functions.sort();
+    let mut result = String::new();
+    let mut missing = 0usize;
+    for (id, offset) in functions {
+        let end = offset_list.function(offset).map_or(text.end, |x| x.end);
+        if let Some(signature) = image.signature(offset, end, max_len) {
+            writeln!(result, "{id}\t{offset}\t{}", signature.to_ida_string())
+                .expect("writing to a string can not fail");
+        } else {
+            missing += 1;
+        }
+    }
+    if missing != 0 {
+        println!("\tfailed to find a unique signature for {missing} functions");
+    }
+
+    let path = output.map_or_else(
+        || root_dir.join(manifest::report_name(module, "signatures", version)),
+        Path::to_path_buf,
+    );
+    fs::write(&path, result).with_context(|| format!("failed to write file: {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Image,
+        Signature,
+    };
+    use crate::common::Offset;
+
+    #[test]
+    fn test_ida_string() {
+        let signature = Signature(vec![
+            Some(0x48),
+            Some(0x8B),
+            Some(0x05),
+            None,
+            None,
+            Some(0xC3),
+        ]);
+        assert_eq!(signature.to_ida_string(), "48 8B 05 ? ? C3");
+    }
+
+    #[test]
+    fn test_shortest_signature() {
+        let bytes = vec![0x48, 0x8B, 0x05, 0x90, 0x48, 0x8B, 0xC3, 0x55, 0x89];
+        let mut wildcards = vec![false; bytes.len()];
+        let image = Image::from_bytes(Offset(0x1000), bytes.clone(), wildcards.clone());
+        let signature = |image: &Image, pos: u32, max_len| {
+            image
+                .signature(Offset(0x1000 + pos), Offset(0x1009), max_len)
+                .map(|x| x.to_ida_string())
+        };
+
+        assert_eq!(signature(&image, 7, 64).as_deref(), Some("55 89"));
+        assert_eq!(signature(&image, 0, 64).as_deref(), Some("48 8B 05"));
+        assert_eq!(signature(&image, 4, 2), None);
+
+        wildcards[5] = true;
+        let image = Image::from_bytes(Offset(0x1000), bytes, wildcards);
+        assert_eq!(signature(&image, 4, 64).as_deref(), Some("48 ? C3"));
+    }
+
+    #[test]
+    fn test_common_prefix() {
+        // more positions share the prefix than are copied out of the index at once
+        let count = Image::MAX_COPIED_CANDIDATES * 2;
+        let mut bytes = [0x48, 0x89, 0x5C, 0x90].repeat(count);
+        bytes[(count - 1) * 4 + 2] = 0x6C;
+        let len = u32::try_from(bytes.len()).expect("image is small");
+        let image = Image::from_bytes(Offset(0x1000), bytes, vec![false; len as usize]);
+        let signature = image
+            .signature(Offset(0x1000 + len - 4), Offset(0x1000 + len), 64)
+            .map(|x| x.to_ida_string());
+        assert_eq!(signature.as_deref(), Some("48 89 6C"));
+    }
+}
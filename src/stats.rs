@@ -0,0 +1,213 @@
+use crate::{
+    addrlib::AddressBins,
+    common::Id,
+    diffs::DiffLists,
+    graph::{
+        Graph,
+        Source,
+    },
+    offsets::{
+        Kind,
+        OffsetLists,
+    },
+};
+use anyhow::Context as _;
+use std::{
+    collections::{
+        BTreeMap,
+        BTreeSet,
+    },
+    fmt::{
+        self,
+        Display,
+        Formatter,
+        Write as _,
+    },
+    fs,
+    path::Path,
+};
+
+#[derive(Default)]
+struct Counts {
+    inherited: usize,
+    fresh: usize,
+}
+
+impl Counts {
+    fn add(&mut self, id: Id, first_fresh_id: Id) {
+        if id < first_fresh_id {
+            self.inherited += 1;
+        } else {
+            self.fresh += 1;
+        }
+    }
+}
+
+impl Display for Counts {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let total = self.inherited + self.fresh;
+        #[allow(clippy::cast_precision_loss)]
+        let percent = if total == 0 {
+            0.0
+        } else {
+            self.inherited as f64 / total as f64 * 100.0
+        };
+        write!(
+            f,
+            "{total} offsets, {} inherited ({percent:.3}%), {} fresh",
+            self.inherited, self.fresh
+        )
+    }
+}
+
+/// Writes a report describing where the ids of every version came from, once all ids have been
+/// assigned.
+///
+/// An id is inherited if it was already allocated by a shipped bin, and fresh otherwise. A shipped
+/// id has vanished from a version if it is present in the reference bin (the bin shipped for the
+/// version itself, or else the newest bin shipped for an older version) but not in the version.
+pub fn write_stats(
+    path: &Path,
+    graph: &Graph,
+    offset_lists: &OffsetLists,
+    diff_lists: &DiffLists,
+    address_bins: &AddressBins,
+    first_fresh_id: Id,
+) -> anyhow::Result<()> {
+    println!("writing stats...");
+
+    let mut result = String::new();
+    macro_rules! line {
+        ($($arg:tt)*) => {
+            writeln!(result, $($arg)*).expect("writing to a string can not fail")
+        };
+    }
+
+    for (version, offset_list) in offset_lists.iter() {
+        let mut total = Counts::default();
+        let mut segments: BTreeMap<&str, Counts> = BTreeMap::new();
+        let mut kinds: BTreeMap<Kind, Counts> = BTreeMap::new();
+        let mut ids = BTreeSet::new();
+        for (offset, mapping) in offset_list.iter() {
+            let id = graph.get(mapping.ix);
+            ids.insert(id);
+            total.add(id, first_fresh_id);
+            let segment = offset_list
+                .segments()
+                .find(*offset)
+                .map_or("<none>", |x| x.name.as_str());
+            segments.entry(segment).or_default().add(id, first_fresh_id);
+            kinds
+                .entry(mapping.kind)
+                .or_default()
+                .add(id, first_fresh_id);
+        }
+
+        line!("{version}: {total}");
+        let reference = address_bins.iter().rfind(|(x, _)| *x <= version);
+        if let Some((reference_version, reference_bin)) = reference {
+            let vanished = reference_bin
+                .iter()
+                .filter(|(id, _)| !ids.contains(id))
+                .count();
+            line!("\tvanished ids from {reference_version}: {vanished}");
+        }
+        line!("\tby segment:");
+        for (segment, counts) in segments {
+            line!("\t\t{segment}: {counts}");
+        }
+        line!("\tby kind:");
+        for (kind, counts) in kinds {
+            line!("\t\t{kind:?}: {counts}");
+        }
+    }
+
+    line!("diffs:");
+    for diff_list in diff_lists.iter() {
+        let left_offsets = offset_lists.get(diff_list.left);
+        let right_offsets = offset_lists.get(diff_list.right);
+        let (mut resolved, mut edges, mut linked, mut total) = (0usize, 0usize, 0usize, 0usize);
+        for diff in diff_list.iter() {
+            total += 1;
+            let left = left_offsets.and_then(|x| x.get(diff.left));
+            let right = right_offsets.and_then(|x| x.get(diff.right));
+            if let (Some(left), Some(right)) = (left, right) {
+                resolved += 1;
+                if graph.has_edge(left.ix, right.ix, Source::Diff) {
+                    edges += 1;
+                }
+                if graph.get(left.ix) == graph.get(right.ix) {
+                    linked += 1;
+                }
+            }
+        }
+        line!(
+            "\t{} -> {}: {total} matches, {resolved} resolved to known offsets, {edges} kept as edges, {linked} share an id",
+            diff_list.left,
+            diff_list.right
+        );
+    }
+
+    fs::write(path, result).with_context(|| format!("failed to write file: {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        addrlib::AddressBins,
+        common,
+        diffs::DiffLists,
+        errors::ParseErrors,
+        manifest::Manifest,
+        offsets::OffsetLists,
+    };
+    use std::fs;
+
+    #[test]
+    fn test_write_stats() -> anyhow::Result<()> {
+        let root = common::test_dir("stats");
+        common::write_idaexport(
+            &root.join("1.10.130"),
+            &[(0x1000, 0x1010), (0x1010, 0x1020)],
+            &[0x3000],
+            &[],
+        )?;
+        common::write_idaexport(
+            &root.join("1.10.138"),
+            &[(0x1000, 0x1010), (0x1010, 0x1020)],
+            &[0x3000],
+            &[],
+        )?;
+        // the last match crosses from `.text` to `.data`, so it is rejected as an edge
+        fs::write(
+            root.join("1.10.130_1.10.138.txt"),
+            "Overall success: 100%\n\n0x140001000\t0x140001000\n0x140002000\t0x140001010\n0x140001010\t0x140003000\n",
+        )?;
+
+        let manifest = Manifest::load(&root, None)?;
+        let mut errors = ParseErrors::default();
+        let (offset_lists, mut graph) = OffsetLists::parse_all(&manifest, &[], &mut errors)?;
+        let diff_lists = DiffLists::parse_all(&manifest, &mut errors)?;
+        graph.add_edges(&offset_lists, &diff_lists)?;
+        let address_bins = AddressBins::parse_all(&manifest, &mut errors)?;
+        let first_fresh_id = address_bins.largest_unused_id();
+        graph.assign_all_ids(first_fresh_id)?;
+
+        let path = root.join("stats.txt");
+        super::write_stats(
+            &path,
+            &graph,
+            &offset_lists,
+            &diff_lists,
+            &address_bins,
+            first_fresh_id,
+        )?;
+        let stats = fs::read_to_string(&path)?;
+        assert!(stats.starts_with("v1.10.130.0: 3 offsets, 0 inherited (0.000%), 3 fresh\n"));
+        assert!(stats.ends_with(
+            "diffs:\n\tv1.10.130.0 -> v1.10.138.0: 3 matches, 2 resolved to known offsets, 1 kept as edges, 1 share an id\n"
+        ));
+        fs::remove_dir_all(root)?;
+        Ok(())
+    }
+}
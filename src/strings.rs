@@ -0,0 +1,299 @@
+use crate::{
+    common::Offset,
+    errors::{
+        FileErrors,
+        ParseErrors,
+    },
+    graph::{
+        Graph,
+        Source,
+    },
+    offsets::{
+        OffsetList,
+        OffsetLists,
+    },
+    xrefs::XrefList,
+};
+use anyhow::Context as _;
+use std::{
+    collections::{
+        BTreeMap,
+        BTreeSet,
+        HashMap,
+    },
+    io::BufRead,
+};
+
+pub struct StringLiteral {
+    pub offset: Offset,
+    pub content: String,
+}
+
+pub struct StringList {
+    strings: Vec<StringLiteral>,
+}
+
+impl StringList {
+    const STRING_PATTERN: &'static str = r"string\t([\dA-Fa-f]+)\t([^\r\n]*)";
+
+    pub fn load(offset_list: &OffsetList, errors: &mut ParseErrors) -> anyhow::Result<Self> {
+        let mut file = offset_list.open("idaexport_string.txt")?;
+        let path = offset_list.directory().join("idaexport_string.txt");
+        Self::parse(
+            &mut file,
+            offset_list.base_address(),
+            &mut errors.file(&path),
+        )
+        .context("failed to parse idaexport_string.txt")
+    }
+
+    fn parse<R: BufRead>(
+        idaexport: &mut R,
+        base_address: u64,
+        errors: &mut FileErrors,
+    ) -> anyhow::Result<Self> {
+        let strings =
+            OffsetList::parse_generic(idaexport, Self::STRING_PATTERN, errors, |captures| {
+                let offset = OffsetList::parse_offset(base_address, &captures[1])?;
+                Ok(StringLiteral {
+                    offset: Offset(offset),
+                    content: captures[2].to_owned(),
+                })
+            })?;
+        Ok(Self { strings })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &StringLiteral> {
+        self.strings.iter()
+    }
+
+    /// Maps the content of each string literal which occurs exactly once to its offset.
+    fn unique(&self) -> HashMap<&str, Offset> {
+        let mut unique = HashMap::new();
+        let mut duplicates = BTreeSet::new();
+        for string in self.iter() {
+            if unique
+                .insert(string.content.as_str(), string.offset)
+                .is_some()
+            {
+                duplicates.insert(string.content.as_str());
+            }
+        }
+        for duplicate in duplicates {
+            unique.remove(duplicate);
+        }
+        unique
+    }
+}
+
+/// Maps each xref target to the set of functions which reference it.
+fn referrers(xrefs: &XrefList, offset_list: &OffsetList) -> BTreeMap<Offset, BTreeSet<Offset>> {
+    let mut referrers: BTreeMap<Offset, BTreeSet<Offset>> = BTreeMap::new();
+    for xref in xrefs.iter() {
+        if let Some(function) = offset_list.function_containing(xref.from) {
+            referrers.entry(xref.to).or_default().insert(function);
+        }
+    }
+    referrers
+}
+
+/// Matches string literals with identical contents that occur exactly once in each version, along
+/// with the single function that references each of them, if there is one.
+pub fn match_strings(
+    graph: &mut Graph,
+    offset_lists: &OffsetLists,
+    errors: &mut ParseErrors,
+) -> anyhow::Result<()> {
+    println!("matching string references...");
+
+    for ((left_version, left_offsets), (right_version, right_offsets)) in
+        offset_lists.adjacent_pairs()
+    {
+        if !left_offsets.has_idaexport() || !right_offsets.has_idaexport() {
+            println!(
+                "\tskipping '{left_version}' -> '{right_version}', which lack idaexport files"
+            );
+            continue;
+        }
+        let mut load = |version, offset_list| -> anyhow::Result<_> {
+            let strings = StringList::load(offset_list, errors)
+                .with_context(|| format!("failed to load strings for version '{version}'"))?;
+            let xrefs = XrefList::load(offset_list, errors)
+                .with_context(|| format!("failed to load xrefs for version '{version}'"))?;
+            Ok((strings, referrers(&xrefs, offset_list)))
+        };
+        let (left_strings, left_referrers) = load(left_version, left_offsets)?;
+        let (right_strings, right_referrers) = load(right_version, right_offsets)?;
+        let left_unique = left_strings.unique();
+        let right_unique = right_strings.unique();
+
+        let correspondence = graph.correspondence(left_offsets, right_offsets);
+        let mut strings = Vec::new();
+        let mut proposals: HashMap<Offset, Option<Offset>> = HashMap::new();
+        let mut reverse: HashMap<Offset, Option<Offset>> = HashMap::new();
+        for (content, left) in &left_unique {
+            let Some(right) = right_unique.get(content) else {
+                continue;
+            };
+            strings.push((*left, *right));
+
+            let single = |referrers: &BTreeMap<Offset, BTreeSet<Offset>>, offset| {
+                referrers
+                    .get(&offset)
+                    .filter(|x| x.len() == 1)
+                    .and_then(|x| x.first().copied())
+            };
+            if let (Some(left), Some(right)) = (
+                single(&left_referrers, *left),
+                single(&right_referrers, *right),
+            ) {
+                for (map, key, value) in
+                    [(&mut proposals, left, right), (&mut reverse, right, left)]
+                {
+                    map.entry(key)
+                        .and_modify(|x| {
+                            if *x != Some(value) {
+                                *x = None;
+                            }
+                        })
+                        .or_insert(Some(value));
+                }
+            }
+        }
+
+        let functions = proposals
+            .into_iter()
+            .filter_map(|(left, right)| right.map(|right| (left, right)))
+            .filter(|(left, right)| reverse.get(right) == Some(&Some(*left)));
+        let mut string_matches = 0usize;
+        let mut function_matches = 0usize;
+        for (is_function, (left, right)) in strings
+            .into_iter()
+            .map(|x| (false, x))
+            .chain(functions.map(|x| (true, x)))
+        {
+            if correspondence.is_left_matched(left) || correspondence.is_right_matched(right) {
+                continue;
+            }
+            let left_node = left_offsets.get(left).map(|x| x.ix);
+            let right_node = right_offsets.get(right).map(|x| x.ix);
+            if let (Some(left_node), Some(right_node)) = (left_node, right_node) {
+                graph.add_edge(left_node, right_node, Source::String);
+                if is_function {
+                    function_matches += 1;
+                } else {
+                    string_matches += 1;
+                }
+            }
+        }
+
+        println!(
+            "\t'{left_version}' -> '{right_version}': {string_matches} strings, {function_matches} functions"
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StringList;
+    use crate::{
+        common,
+        errors::ParseErrors,
+        manifest::Manifest,
+        offsets::OffsetLists,
+    };
+    use std::{
+        fmt::Write as _,
+        fs,
+        io::BufReader,
+        path::Path,
+    };
+
+    #[test]
+    fn test_strings() -> anyhow::Result<()> {
+        let mut buffer = BufReader::new(
+            &br"version	1
+string	142C18670	bShowCompass:Interface
+string	142C186A0	Failed to load %s	(%d)
+string	142C186C0	bShowCompass:Interface
+"[..],
+        );
+        let strings = StringList::parse(
+            &mut buffer,
+            0x1_4000_0000,
+            &mut ParseErrors::default().file(Path::new("test")),
+        )?;
+        let result = strings
+            .iter()
+            .map(|x| (x.offset.0, x.content.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            result,
+            [
+                (0x2C1_8670, "bShowCompass:Interface"),
+                (0x2C1_86A0, "Failed to load %s\t(%d)"),
+                (0x2C1_86C0, "bShowCompass:Interface"),
+            ]
+        );
+        let unique = strings.unique();
+        assert_eq!(unique.len(), 1);
+        assert_eq!(unique["Failed to load %s\t(%d)"].0, 0x2C1_86A0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_match_strings() -> anyhow::Result<()> {
+        let root = common::test_dir("match-strings");
+        // the second string has two referrers, and the last two strings share their contents
+        for (version, text, data) in [("1.10.130", 0x1000, 0x3000), ("1.10.138", 0x1100, 0x3040)] {
+            let directory = root.join(version);
+            common::write_idaexport(
+                &directory,
+                &[
+                    (text, text + 0x10),
+                    (text + 0x10, text + 0x20),
+                    (text + 0x20, text + 0x30),
+                ],
+                &[data, data + 0x10, data + 0x20, data + 0x30],
+                &[
+                    (text + 4, data),
+                    (text + 0x14, data + 0x10),
+                    (text + 0x24, data + 0x10),
+                ],
+            )?;
+            let mut strings = "version\t1\n".to_owned();
+            for (offset, content) in [
+                (data, "unique"),
+                (data + 0x10, "shared"),
+                (data + 0x20, "dup"),
+                (data + 0x30, "dup"),
+            ] {
+                writeln!(strings, "string\t14000{offset:04X}\t{content}")?;
+            }
+            fs::write(directory.join("idaexport_string.txt"), strings)?;
+        }
+
+        let manifest = Manifest::load(&root, None)?;
+        let (offset_lists, mut graph) =
+            OffsetLists::parse_all(&manifest, &[], &mut ParseErrors::default())?;
+        super::match_strings(&mut graph, &offset_lists, &mut ParseErrors::default())?;
+
+        let ((_, left), (_, right)) = offset_lists
+            .adjacent_pairs()
+            .next()
+            .expect("expected two versions");
+        let result = graph
+            .correspondence(left, right)
+            .iter()
+            .map(|(x, y)| (x.0, y.0))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            result,
+            [(0x1000, 0x1100), (0x3000, 0x3040), (0x3010, 0x3050)]
+        );
+        fs::remove_dir_all(root)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,357 @@
+use crate::{
+    common::{
+        Id,
+        Offset,
+        Version,
+    },
+    errors::FileErrors,
+};
+use anyhow::Context as _;
+use byteorder::{
+    LittleEndian,
+    ReadBytesExt as _,
+    WriteBytesExt as _,
+};
+use std::{
+    fmt::Write as _,
+    io::{
+        BufRead,
+        Read,
+        Write,
+    },
+};
+
+/// How a value is encoded within a compressed address bin, relative to the value before it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Delta {
+    Full(u64),
+    Next,
+    AddU8(u8),
+    SubU8(u8),
+    AddU16(u16),
+    SubU16(u16),
+    U16(u16),
+    U32(u32),
+}
+
+impl Delta {
+    fn new(previous: u64, value: u64) -> Self {
+        let up = value.checked_sub(previous);
+        let down = previous.checked_sub(value);
+        if up == Some(1) {
+            Self::Next
+        } else if let Some(x) = up.and_then(|x| u8::try_from(x).ok()) {
+            Self::AddU8(x)
+        } else if let Some(x) = down.and_then(|x| u8::try_from(x).ok()) {
+            Self::SubU8(x)
+        } else if let Some(x) = up.and_then(|x| u16::try_from(x).ok()) {
+            Self::AddU16(x)
+        } else if let Some(x) = down.and_then(|x| u16::try_from(x).ok()) {
+            Self::SubU16(x)
+        } else if let Ok(x) = u16::try_from(value) {
+            Self::U16(x)
+        } else if let Ok(x) = u32::try_from(value) {
+            Self::U32(x)
+        } else {
+            Self::Full(value)
+        }
+    }
+
+    fn is_relative(self) -> bool {
+        matches!(
+            self,
+            Self::Next | Self::AddU8(_) | Self::SubU8(_) | Self::AddU16(_) | Self::SubU16(_)
+        )
+    }
+
+    fn tag(self) -> u8 {
+        match self {
+            Self::Full(_) => 0,
+            Self::Next => 1,
+            Self::AddU8(_) => 2,
+            Self::SubU8(_) => 3,
+            Self::AddU16(_) => 4,
+            Self::SubU16(_) => 5,
+            Self::U16(_) => 6,
+            Self::U32(_) => 7,
+        }
+    }
+
+    fn read<R: Read>(src: &mut R, tag: u8, previous: u64) -> anyhow::Result<u64> {
+        let value = match tag {
+            0 => Some(src.read_u64::<LittleEndian>()?),
+            1 => previous.checked_add(1),
+            2 => previous.checked_add(src.read_u8()?.into()),
+            3 => previous.checked_sub(src.read_u8()?.into()),
+            4 => previous.checked_add(src.read_u16::<LittleEndian>()?.into()),
+            5 => previous.checked_sub(src.read_u16::<LittleEndian>()?.into()),
+            6 => Some(src.read_u16::<LittleEndian>()?.into()),
+            7 => Some(src.read_u32::<LittleEndian>()?.into()),
+            _ => anyhow::bail!("unknown delta: {tag}"),
+        };
+        value.with_context(|| format!("delta {tag} moves {previous} out of range"))
+    }
+
+    fn write<W: Write>(self, dst: &mut W) -> std::io::Result<()> {
+        match self {
+            Self::Full(x) => dst.write_u64::<LittleEndian>(x),
+            Self::Next => Ok(()),
+            Self::AddU8(x) | Self::SubU8(x) => dst.write_u8(x),
+            Self::AddU16(x) | Self::SubU16(x) | Self::U16(x) => dst.write_u16::<LittleEndian>(x),
+            Self::U32(x) => dst.write_u32::<LittleEndian>(x),
+        }
+    }
+}
+
+/// Reads a compressed address bin, checking that its header matches the expected format and
+/// pointer size.
+pub fn read_compressed<R: Read>(
+    src: &mut R,
+    format: i32,
+    pointer_size: u32,
+) -> anyhow::Result<(Version, Vec<(Id, Offset)>)> {
+    let mut read_i32 = || {
+        src.read_i32::<LittleEndian>()
+            .context("error while reading address bin header")
+    };
+    let found = read_i32().context("failed to read format")?;
+    if found != format {
+        anyhow::bail!("expected format {format}, but found format {found}");
+    }
+    let mut version = [0u16; 4];
+    for x in &mut version {
+        *x = read_i32()?
+            .try_into()
+            .context("read a version part out of range")?;
+    }
+    let name_len = read_i32().context("failed to read name length")?;
+    let mut name = vec![0; name_len.try_into().context("read a negative name length")?];
+    src.read_exact(&mut name).context("failed to read name")?;
+    let found = src
+        .read_i32::<LittleEndian>()
+        .context("failed to read pointer size")?;
+    if u32::try_from(found).ok() != Some(pointer_size) {
+        anyhow::bail!("expected pointer size {pointer_size}, but found pointer size {found}");
+    }
+    let len = src
+        .read_i32::<LittleEndian>()
+        .context("failed to read len")?;
+
+    let pointer_size = u64::from(pointer_size);
+    let mut mappings = Vec::new();
+    let (mut previous_id, mut previous_offset) = (0, 0);
+    for i in 0..len {
+        let mut read = || -> anyhow::Result<_> {
+            let tag = src.read_u8()?;
+            let id = Delta::read(src, tag & 0xF, previous_id)?;
+            let scaled = tag & 0x80 != 0;
+            let base = if scaled {
+                previous_offset / pointer_size
+            } else {
+                previous_offset
+            };
+            let mut offset = Delta::read(src, (tag >> 4) & 0x7, base)?;
+            if scaled {
+                offset = offset
+                    .checked_mul(pointer_size)
+                    .context("read a scaled offset out of range")?;
+            }
+            Ok((id, offset))
+        };
+        let (id, offset) = read().with_context(|| format!("failed to read entry {i}"))?;
+        mappings.push((
+            Id::try_from(id).with_context(|| format!("entry {i}: read an invalid id"))?,
+            Offset(
+                u32::try_from(offset)
+                    .with_context(|| format!("entry {i}: read an offset too large for a u32"))?,
+            ),
+        ));
+        (previous_id, previous_offset) = (id, offset);
+    }
+    Ok((Version::from(version), mappings))
+}
+
+/// Writes a compressed address bin, encoding every mapping as a delta from the one before it.
+/// Offsets are scaled down by the pointer size whenever that allows for a smaller delta.
+pub fn write_compressed<W: Write>(
+    dst: &mut W,
+    version: Version,
+    mappings: &[(Id, Offset)],
+    format: i32,
+    executable: &str,
+    pointer_size: u32,
+) -> anyhow::Result<()> {
+    dst.write_i32::<LittleEndian>(format)?;
+    for i in 0..4 {
+        dst.write_i32::<LittleEndian>(version[i].into())?;
+    }
+    dst.write_i32::<LittleEndian>(executable.len().try_into()?)?;
+    dst.write_all(executable.as_bytes())?;
+    dst.write_i32::<LittleEndian>(pointer_size.try_into()?)?;
+    dst.write_i32::<LittleEndian>(
+        mappings
+            .len()
+            .try_into()
+            .context("too many mappings for a compressed address bin")?,
+    )?;
+
+    let pointer_size = u64::from(pointer_size);
+    let (mut previous_id, mut previous_offset) = (0, 0);
+    for (id, offset) in mappings {
+        let (id, offset) = (id.get(), u64::from(offset.0));
+        let id_delta = Delta::new(previous_id, id);
+        let mut offset_delta = Delta::new(previous_offset, offset);
+        let mut scaled = false;
+        if !offset_delta.is_relative() && offset % pointer_size == 0 {
+            let delta = Delta::new(previous_offset / pointer_size, offset / pointer_size);
+            if delta.is_relative() {
+                (offset_delta, scaled) = (delta, true);
+            }
+        }
+        let tag = id_delta.tag() | offset_delta.tag() << 4 | if scaled { 0x80 } else { 0 };
+        dst.write_u8(tag)?;
+        id_delta.write(dst)?;
+        offset_delta.write(dst)?;
+        (previous_id, previous_offset) = (id, offset);
+    }
+    Ok(())
+}
+
+/// Reads a csv address bin, skipping the rows which fail to parse if lenient.
+pub fn read_csv<R: BufRead>(
+    src: &mut R,
+    errors: &mut FileErrors,
+) -> anyhow::Result<(Version, Vec<(Id, Offset)>)> {
+    let mut lines = src.lines();
+    let mut read_line = || -> anyhow::Result<String> {
+        lines
+            .next()
+            .context("reached end of file before finding the header")?
+            .context("error while reading csv")
+    };
+    let header = read_line()?;
+    if header.trim() != "id,offset" {
+        anyhow::bail!("expected header `id,offset`: {header}");
+    }
+    let first = read_line()?;
+    let (len, version) = first
+        .trim()
+        .split_once(',')
+        .with_context(|| format!("expected `count,version` on line 2: {first}"))?;
+    let len: usize = len
+        .parse()
+        .with_context(|| format!("failed to parse count: {len}"))?;
+    let version = version.parse()?;
+
+    let mut mappings = Vec::new();
+    for (line, row) in (3..).zip(lines) {
+        let row = row.with_context(|| format!("failed to read line {line}"))?;
+        if row.trim().is_empty() {
+            continue;
+        }
+        let mapping = row
+            .trim()
+            .split_once(',')
+            .context("expected `id,offset`")
+            .and_then(|(id, offset)| {
+                let id = id
+                    .parse::<u64>()
+                    .ok()
+                    .and_then(|x| Id::try_from(x).ok())
+                    .with_context(|| format!("failed to parse id: {id}"))?;
+                let offset = u32::from_str_radix(offset, 16)
+                    .with_context(|| format!("failed to parse offset: {offset}"))?;
+                Ok((id, Offset(offset)))
+            });
+        mappings.extend(errors.check(line, mapping)?);
+    }
+    if mappings.len() != len {
+        println!(
+            "\tcsv claims to hold {len} mappings, but holds {}",
+            mappings.len()
+        );
+    }
+    Ok((version, mappings))
+}
+
+pub fn write_csv(version: Version, mappings: &[(Id, Offset)]) -> String {
+    let mut result = format!(
+        "id,offset\n{},{}.{}.{}.{}\n",
+        mappings.len(),
+        version[0],
+        version[1],
+        version[2],
+        version[3]
+    );
+    for (id, offset) in mappings {
+        writeln!(result, "{id},{:X}", offset.0).expect("writing to a string can not fail");
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Delta;
+    use crate::{
+        common::{
+            Id,
+            Offset,
+        },
+        errors::ParseErrors,
+    };
+    use std::{
+        io::BufReader,
+        path::Path,
+    };
+
+    #[test]
+    fn test_delta() {
+        assert_eq!(Delta::new(5, 6), Delta::Next);
+        assert_eq!(Delta::new(5, 7), Delta::AddU8(2));
+        assert_eq!(Delta::new(0x1000, 0x10), Delta::SubU16(0xFF0));
+        assert_eq!(Delta::new(0, 0x12345), Delta::U32(0x12345));
+        assert_eq!(Delta::new(0x12345, 0x10), Delta::U16(0x10));
+        assert_eq!(Delta::new(0, 1 << 40), Delta::Full(1 << 40));
+    }
+
+    #[test]
+    fn test_compressed_round_trip() -> anyhow::Result<()> {
+        let id = |x: u64| Id::try_from(x);
+        let mappings = [
+            (id(1)?, Offset(0x1000)),
+            (id(2)?, Offset(0x1010)),
+            (id(3)?, Offset(0x2C0_0000)),
+            (id(500)?, Offset(0x2C0_1000)),
+            (id(1 << 40)?, Offset(0x10)),
+        ];
+        let mut bytes = Vec::new();
+        super::write_compressed(
+            &mut bytes,
+            "1.6.1170".parse()?,
+            &mappings,
+            2,
+            "SkyrimSE.exe",
+            8,
+        )?;
+        let (version, result) = super::read_compressed(&mut &bytes[..], 2, 8)?;
+        assert!(version == "1.6.1170".parse()?);
+        assert!(result == mappings);
+        assert!(super::read_compressed(&mut &bytes[..], 1, 8).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_csv_round_trip() -> anyhow::Result<()> {
+        let id = |x: u64| Id::try_from(x);
+        let mappings = [(id(1)?, Offset(0x1000)), (id(20)?, Offset(0x1A_2B3C))];
+        let text = super::write_csv("1.4.15".parse()?, &mappings);
+        assert_eq!(text, "id,offset\n2,1.4.15.0\n1,1000\n20,1A2B3C\n");
+        let (version, result) = super::read_csv(
+            &mut BufReader::new(text.as_bytes()),
+            &mut ParseErrors::default().file(Path::new("test")),
+        )?;
+        assert!(version == "1.4.15".parse()?);
+        assert!(result == mappings);
+        Ok(())
+    }
+}
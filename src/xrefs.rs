@@ -0,0 +1,322 @@
+use crate::{
+    common::Offset,
+    errors::{
+        FileErrors,
+        ParseErrors,
+    },
+    graph::{
+        Graph,
+        Source,
+    },
+    offsets::{
+        OffsetList,
+        OffsetLists,
+    },
+};
+use anyhow::Context as _;
+use std::{
+    collections::{
+        BTreeMap,
+        HashMap,
+    },
+    io::BufRead,
+};
+
+pub struct Xref {
+    pub from: Offset,
+    pub to: Offset,
+}
+
+pub struct XrefList {
+    xrefs: Vec<Xref>,
+}
+
+impl XrefList {
+    const XREF_PATTERN: &'static str = r"xref\t([\dA-Fa-f]+)\t([\dA-Fa-f]+)";
+
+    pub fn load(offset_list: &OffsetList, errors: &mut ParseErrors) -> anyhow::Result<Self> {
+        let mut file = offset_list.open("idaexport_xrefs.txt")?;
+        let path = offset_list.directory().join("idaexport_xrefs.txt");
+        Self::parse(
+            &mut file,
+            offset_list.base_address(),
+            &mut errors.file(&path),
+        )
+        .context("failed to parse idaexport_xrefs.txt")
+    }
+
+    fn parse<R: BufRead>(
+        idaexport: &mut R,
+        base_address: u64,
+        errors: &mut FileErrors,
+    ) -> anyhow::Result<Self> {
+        let mut xrefs =
+            OffsetList::parse_generic(idaexport, Self::XREF_PATTERN, errors, |captures| {
+                let from = OffsetList::parse_offset(base_address, &captures[1])?;
+                let to = OffsetList::parse_offset(base_address, &captures[2])?;
+                Ok(Xref {
+                    from: Offset(from),
+                    to: Offset(to),
+                })
+            })?;
+        xrefs.sort_by_key(|x| (x.from, x.to));
+        Ok(Self { xrefs })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Xref> {
+        self.xrefs.iter()
+    }
+
+    /// Groups the targets of all xrefs by the function they originate from. The targets of each
+    /// function are ordered by the position of the referencing instruction.
+    pub fn by_function(&self, offset_list: &OffsetList) -> BTreeMap<Offset, Vec<Offset>> {
+        let mut functions: BTreeMap<Offset, Vec<Offset>> = BTreeMap::new();
+        for xref in self.iter() {
+            if let Some(function) = offset_list.function_containing(xref.from) {
+                functions.entry(function).or_default().push(xref.to);
+            }
+        }
+        functions
+    }
+}
+
+/// Matches offsets which are referenced from the same position within already matched functions.
+/// Only references from within the bounds of a function count, and a function target is never
+/// matched with a non-function target.
+///
+/// Runs repeatedly over each pair of consecutive versions until a pass finds no new matches, since
+/// each match may itself be a function whose references can then be followed.
+pub fn propagate(
+    graph: &mut Graph,
+    offset_lists: &OffsetLists,
+    errors: &mut ParseErrors,
+) -> anyhow::Result<()> {
+    println!("propagating matches through xrefs...");
+
+    let mut xrefs = BTreeMap::new();
+    for (version, offset_list) in offset_lists.iter() {
+        if !offset_list.has_idaexport() {
+            println!("\tskipping '{version}', which has no idaexport files");
+            continue;
+        }
+        let functions = XrefList::load(offset_list, errors)
+            .with_context(|| format!("failed to load xrefs for version '{version}'"))?
+            .by_function(offset_list);
+        xrefs.insert(*version, functions);
+    }
+
+    for ((left_version, left_offsets), (right_version, right_offsets)) in
+        offset_lists.adjacent_pairs()
+    {
+        let (Some(left_xrefs), Some(right_xrefs)) =
+            (xrefs.get(left_version), xrefs.get(right_version))
+        else {
+            continue;
+        };
+        for pass in 1.. {
+            let correspondence = graph.correspondence(left_offsets, right_offsets);
+            let mut proposals: HashMap<Offset, Option<Offset>> = HashMap::new();
+            let mut reverse: HashMap<Offset, Option<Offset>> = HashMap::new();
+            for (left_function, left_targets) in left_xrefs {
+                let Some(right_targets) = correspondence
+                    .left(*left_function)
+                    .and_then(|x| right_xrefs.get(&x))
+                else {
+                    continue;
+                };
+                if left_targets.len() != right_targets.len() {
+                    continue;
+                }
+                // targets are paired by position, so a pair which disagrees on whether it points
+                // to a function is taken as a sign that the references have shifted
+                for (left, right) in left_targets.iter().zip(right_targets) {
+                    if correspondence.is_left_matched(*left)
+                        || correspondence.is_right_matched(*right)
+                        || left_offsets.get(*left).is_none()
+                        || right_offsets.get(*right).is_none()
+                        || left_offsets.is_function(*left) != right_offsets.is_function(*right)
+                    {
+                        continue;
+                    }
+                    for (map, key, value) in [
+                        (&mut proposals, *left, *right),
+                        (&mut reverse, *right, *left),
+                    ] {
+                        map.entry(key)
+                            .and_modify(|x| {
+                                if *x != Some(value) {
+                                    *x = None;
+                                }
+                            })
+                            .or_insert(Some(value));
+                    }
+                }
+            }
+
+            let mut matches = 0usize;
+            for (left, right) in proposals {
+                if let Some(right) = right {
+                    if reverse.get(&right) == Some(&Some(left)) {
+                        let left_node = left_offsets.get(left).map(|x| x.ix);
+                        let right_node = right_offsets.get(right).map(|x| x.ix);
+                        if let (Some(left_node), Some(right_node)) = (left_node, right_node) {
+                            graph.add_edge(left_node, right_node, Source::Xref);
+                            matches += 1;
+                        }
+                    }
+                }
+            }
+
+            println!("\t'{left_version}' -> '{right_version}' pass {pass}: {matches} new matches");
+            if matches == 0 {
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::XrefList;
+    use crate::{
+        common::{
+            self,
+            Offset,
+        },
+        errors::ParseErrors,
+        graph::Source,
+        manifest::Manifest,
+        offsets::OffsetLists,
+    };
+    use std::{
+        fs,
+        io::BufReader,
+        path::Path,
+    };
+
+    #[test]
+    fn test_propagate() -> anyhow::Result<()> {
+        let root = common::test_dir("propagate");
+        // the second function references one global from past its end, and the third references
+        // a function on the left but a global on the right
+        common::write_idaexport(
+            &root.join("1.10.130"),
+            &[
+                (0x1000, 0x1040),
+                (0x1040, 0x1050),
+                (0x1080, 0x10A0),
+                (0x10A0, 0x10C0),
+            ],
+            &[0x3000, 0x3008, 0x3010, 0x3040, 0x3050],
+            &[
+                (0x1004, 0x3000),
+                (0x1008, 0x3008),
+                (0x1044, 0x3010),
+                (0x1060, 0x3040),
+                (0x1084, 0x10A0),
+            ],
+        )?;
+        common::write_idaexport(
+            &root.join("1.10.138"),
+            &[
+                (0x1010, 0x1050),
+                (0x1050, 0x1060),
+                (0x1090, 0x10B0),
+                (0x10B0, 0x10D0),
+            ],
+            &[0x3020, 0x3028, 0x3030, 0x3060, 0x3070],
+            &[
+                (0x1014, 0x3020),
+                (0x1018, 0x3028),
+                (0x1054, 0x3030),
+                (0x1094, 0x3070),
+            ],
+        )?;
+
+        let manifest = Manifest::load(&root, None)?;
+        let (offset_lists, mut graph) =
+            OffsetLists::parse_all(&manifest, &[], &mut ParseErrors::default())?;
+        let ((_, left), (_, right)) = offset_lists
+            .adjacent_pairs()
+            .next()
+            .expect("expected two versions");
+        for (x, y) in [(0x1000, 0x1010), (0x1040, 0x1050), (0x1080, 0x1090)] {
+            let x = left.get(Offset(x)).expect("expected left function").ix;
+            let y = right.get(Offset(y)).expect("expected right function").ix;
+            graph.add_edge(x, y, Source::Diff);
+        }
+        super::propagate(&mut graph, &offset_lists, &mut ParseErrors::default())?;
+
+        let result = graph
+            .correspondence(left, right)
+            .iter()
+            .map(|(x, y)| (x.0, y.0))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            result,
+            [
+                (0x1000, 0x1010),
+                (0x1040, 0x1050),
+                (0x1080, 0x1090),
+                (0x3000, 0x3020),
+                (0x3008, 0x3028),
+                (0x3010, 0x3030),
+            ]
+        );
+        fs::remove_dir_all(root)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_xrefs() -> anyhow::Result<()> {
+        let mut buffer = BufReader::new(
+            &br"version	1
+xref	140001004	142C18670
+xref	140001000	1436CB000
+xref	14000104B	140001000
+"[..],
+        );
+        let result = XrefList::parse(
+            &mut buffer,
+            0x1_4000_0000,
+            &mut ParseErrors::default().file(Path::new("test")),
+        )?
+        .iter()
+        .map(|x| (x.from.0, x.to.0))
+        .collect::<Vec<_>>();
+        assert_eq!(
+            result,
+            [(0x1000, 0x36C_B000), (0x1004, 0x2C1_8670), (0x104B, 0x1000)]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_lenient_xrefs() -> anyhow::Result<()> {
+        let source = &br"version	1
+xref	140001004	142C18670
+xref	14000100G	1436CB000
+xref	14000104B	140001000
+"[..];
+        let path = Path::new("idaexport_xrefs.txt");
+        let mut strict = ParseErrors::default();
+        assert!(XrefList::parse(
+            &mut BufReader::new(source),
+            0x1_4000_0000,
+            &mut strict.file(path)
+        )
+        .is_err());
+
+        let mut lenient = ParseErrors::lenient(10);
+        let result = XrefList::parse(
+            &mut BufReader::new(source),
+            0x1_4000_0000,
+            &mut lenient.file(path),
+        )?;
+        assert_eq!(result.iter().count(), 2);
+        assert_eq!(lenient.lines(), [(path, 3)]);
+        Ok(())
+    }
+}